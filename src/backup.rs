@@ -0,0 +1,223 @@
+//! Copies of Steam config files taken before they're changed. Each backup is a directory
+//! named after its id, the UTC time it was taken, containing the copy and the original path.
+//! ```text
+//! backups/
+//!     2026-10-17_15-30-12.123/
+//!         config.vdf
+//!         source          /home/user/.steam/root/config/config.vdf
+//! ```
+use crate::Result;
+use log::warn;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SOURCE_FILE: &str = "source";
+
+#[derive(Debug, Serialize)]
+pub struct Backup {
+    pub id: String,
+    /// File that was backed up
+    pub source: PathBuf,
+    /// Location of the copy
+    pub path: PathBuf,
+    /// Size of the copy in bytes
+    pub size: u64,
+}
+
+/// Converts days since the Unix epoch to a (year, month, day) civil date
+/// https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+pub(crate) fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    (year, month, day)
+}
+
+/// `YYYY-MM-DD_hh-mm-ss.mmm` in UTC, which sorts chronologically
+fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days(secs / 86400);
+    let secs_of_day = secs % 86400;
+
+    format!(
+        "{:04}-{:02}-{:02}_{:02}-{:02}-{:02}.{:03}",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+pub struct BackupStore {
+    dir: PathBuf,
+}
+
+impl BackupStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// `$XDG_STATE_HOME/proton-usage/backups`, defaulting to `~/.local/state`
+    pub fn open_default() -> Result<Self> {
+        let state_dir = dirs::state_dir()
+            .or_else(|| dirs::home_dir().map(|home| home.join(".local/state")))
+            .ok_or("Couldn't find the state directory to keep backups in")?;
+
+        Ok(Self::new(state_dir.join("proton-usage/backups")))
+    }
+
+    /// Copies `source` into a new backup
+    pub fn create(&self, source: &Path) -> Result<Backup> {
+        let file_name = source
+            .file_name()
+            .ok_or_else(|| format!("'{}' isn't a file", source.display()))?;
+        let source = source.canonicalize()?;
+        fs::create_dir_all(&self.dir)?;
+
+        let timestamp = timestamp(SystemTime::now());
+        let mut id = timestamp.clone();
+        let mut attempt = 1;
+        let dir = loop {
+            let dir = self.dir.join(&id);
+            match fs::create_dir(&dir) {
+                Ok(()) => break dir,
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    attempt += 1;
+                    id = format!("{}-{}", timestamp, attempt);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        };
+
+        let path = dir.join(file_name);
+        let size = fs::copy(&source, &path)
+            .map_err(|e| format!("Couldn't back up '{}': {}", source.display(), e))?;
+        fs::write(dir.join(SOURCE_FILE), source.to_string_lossy().as_bytes())?;
+        log::info!("Backed up '{}' to '{}'", source.display(), path.display());
+
+        Ok(Backup {
+            id,
+            source,
+            path,
+            size,
+        })
+    }
+
+    fn read(&self, id: &str) -> Result<Backup> {
+        let dir = self.dir.join(id);
+        let source = PathBuf::from(fs::read_to_string(dir.join(SOURCE_FILE))?);
+        let path = dir.join(source.file_name().ok_or("Invalid source path")?);
+        let size = fs::metadata(&path)?.len();
+
+        Ok(Backup {
+            id: id.to_string(),
+            source,
+            path,
+            size,
+        })
+    }
+
+    /// All backups, oldest first
+    pub fn list(&self) -> Result<Vec<Backup>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Couldn't read '{}': {}", self.dir.display(), e).into()),
+        };
+        let mut backups: Vec<Backup> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.path().is_dir())
+            .filter_map(|entry| {
+                let id = entry.file_name().to_string_lossy().into_owned();
+                self.read(&id)
+                    .map_err(|e| warn!("Skipping invalid backup '{}': {}", id, e))
+                    .ok()
+            })
+            .collect();
+        backups.sort_by(|a, b| a.id.cmp(&b.id));
+
+        Ok(backups)
+    }
+
+    pub fn find(&self, id: &str) -> Result<Backup> {
+        if id.is_empty() || id.contains(['/', '\\']) || id.starts_with('.') {
+            return Err(format!("Invalid backup id '{}'", id).into());
+        }
+
+        self.read(id)
+            .map_err(|e| format!("Couldn't find backup '{}': {}", id, e).into())
+    }
+
+    /// Removes all but the newest `keep` backups. Returns the removed backups.
+    pub fn prune(&self, keep: usize) -> Result<Vec<Backup>> {
+        let mut backups = self.list()?;
+        let remove = backups.len().saturating_sub(keep);
+        let removed: Vec<Backup> = backups.drain(..remove).collect();
+        for backup in &removed {
+            fs::remove_dir_all(self.dir.join(&backup.id))?;
+        }
+
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn formats_timestamps_in_utc() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_210_096_789);
+
+        assert_eq!(timestamp(time), "2024-02-29_12-34-56.789");
+        assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01_00-00-00.000");
+    }
+
+    #[test]
+    fn creates_lists_and_prunes_backups() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let source = dir.join("config.vdf");
+        fs::write(&source, "\"a\" \"1\"").unwrap();
+        let store = BackupStore::new(dir.join("backups"));
+
+        let first = store.create(&source).unwrap();
+        let second = store.create(&source).unwrap();
+        let third = store.create(&source).unwrap();
+
+        let ids: Vec<String> = store.list().unwrap().into_iter().map(|b| b.id).collect();
+        assert_eq!(ids, [first.id.as_str(), &second.id, &third.id]);
+        assert_eq!(fs::read_to_string(&first.path).unwrap(), "\"a\" \"1\"");
+        assert_eq!(
+            store.find(&second.id).unwrap().source,
+            source.canonicalize().unwrap()
+        );
+        assert_eq!(first.size, 7);
+
+        let removed: Vec<String> = store.prune(1).unwrap().into_iter().map(|b| b.id).collect();
+        assert_eq!(removed, [first.id, second.id]);
+        assert_eq!(store.list().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn rejects_ids_outside_the_store() {
+        let store = BackupStore::new(PathBuf::from("/nonexistent"));
+
+        assert!(store.find("../config").is_err());
+        assert!(store.find("").is_err());
+    }
+}
@@ -0,0 +1,83 @@
+//! Delimiter separated output (CSV, TSV)
+use std::io::{self, Write};
+
+/// A flat, row per record representation
+pub trait Rows {
+    fn headers(&self) -> &'static [&'static str];
+    fn rows(&self) -> Vec<Vec<String>>;
+}
+
+/// Fields containing the delimiter, quotes or line breaks are quoted, with quotes doubled
+fn write_field(out: &mut impl Write, field: &str, delimiter: char) -> io::Result<()> {
+    if field.contains([delimiter, '"', '\n', '\r']) {
+        write!(out, "\"{}\"", field.replace('"', "\"\""))
+    } else {
+        write!(out, "{}", field)
+    }
+}
+
+fn write_row<'a>(
+    out: &mut impl Write,
+    fields: impl Iterator<Item = &'a str>,
+    delimiter: char,
+) -> io::Result<()> {
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            write!(out, "{}", delimiter)?;
+        }
+        write_field(out, field, delimiter)?;
+    }
+
+    writeln!(out)
+}
+
+/// Writes a header row followed by every row
+pub fn write(out: &mut impl Write, table: &impl Rows, delimiter: char) -> io::Result<()> {
+    write_row(out, table.headers().iter().copied(), delimiter)?;
+    for row in table.rows() {
+        write_row(out, row.iter().map(String::as_str), delimiter)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Table(Vec<Vec<String>>);
+    impl Rows for Table {
+        fn headers(&self) -> &'static [&'static str] {
+            &["a", "b"]
+        }
+        fn rows(&self) -> Vec<Vec<String>> {
+            self.0.clone()
+        }
+    }
+
+    fn to_string(table: &Table, delimiter: char) -> String {
+        let mut out = Vec::new();
+        write(&mut out, table, delimiter).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn quotes_fields_with_delimiters_and_quotes() {
+        let table = Table(vec![vec![
+            "plain".to_string(),
+            r#"WINEDLLOVERRIDES="d3d9=n,b" %command%"#.to_string(),
+        ]]);
+
+        assert_eq!(
+            to_string(&table, ','),
+            "a,b\nplain,\"WINEDLLOVERRIDES=\"\"d3d9=n,b\"\" %command%\"\n"
+        );
+    }
+
+    #[test]
+    fn only_quotes_tsv_fields_when_needed() {
+        let table = Table(vec![vec!["a,b".to_string(), "c\td".to_string()]]);
+
+        assert_eq!(to_string(&table, '\t'), "a\tb\na,b\t\"c\td\"\n");
+    }
+}
@@ -1,169 +1,2651 @@
-mod steam;
+pub mod backup;
+pub mod delimited;
+pub mod steam;
 
+use crate::backup::{Backup, BackupStore};
+use crate::delimited::Rows;
+use crate::steam::app_info::{
+    app_info_path, AppInfo, SteamPlayManifest, STEAM_PLAY_MANIFEST_APP_ID,
+};
+use crate::steam::app_manifest::{AppManifest, StateFlags};
+use crate::steam::compat_data::{CompatData, PrefixWarning};
+use crate::steam::installed_tools::{InstalledTool, ToolFamily};
+use crate::steam::launch_options::{self, quote, EnvVar, ParsedLaunchOptions, Severity, Wrapper};
+use crate::steam::locator::SteamInstall;
 use crate::steam::registry::Registry;
-use crate::steam::AppId;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use crate::steam::shortcuts::Shortcut;
+use crate::steam::vdf_edit::Document;
+use crate::steam::{AppId, CompatToolMapping, SteamId64, UserId};
+use regex::Regex;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::fmt::{Display, Formatter};
-use std::io::BufRead;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::{fmt, fs};
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 const INDENT_WIDTH: usize = 4;
 
-pub struct CompatToolConfig(BTreeMap<String, Vec<App>>);
+#[derive(Serialize)]
+pub struct CompatToolConfig {
+    /// The "use this tool for all other titles" setting
+    pub default_tool: Option<DefaultTool>,
+    pub tools: BTreeMap<String, MappedTool>,
+}
 impl Display for CompatToolConfig {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        for (i, (compat_tool, apps)) in self.0.iter().enumerate() {
-            writeln!(f, "{}", compat_tool)?;
+        if let Some(default) = &self.default_tool {
+            write!(f, "Default: ")?;
+            write_tool_name(f, &default.name, default.tool.as_ref())?;
+            if !self.tools.is_empty() {
+                writeln!(f)?;
+            }
+        }
+
+        for (i, (compat_tool, mapped)) in self.tools.iter().enumerate() {
+            write_tool_name(f, compat_tool, mapped.tool.as_ref())?;
+
+            for app in &mapped.apps {
+                write!(f, "{:i$}", "", i = INDENT_WIDTH)?;
+                app.fmt(f)?;
+            }
+
+            if i < self.tools.len() - 1 {
+                writeln!(f)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+const COMPAT_TOOL_HEADERS: [&str; 4] = ["tool", "app_id", "app_name", "install_state"];
+
+impl Rows for CompatToolConfig {
+    fn headers(&self) -> &'static [&'static str] {
+        &COMPAT_TOOL_HEADERS
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.tools
+            .iter()
+            .flat_map(|(tool, mapped)| {
+                mapped.apps.iter().map(move |app| {
+                    vec![
+                        tool.to_string(),
+                        app.id.to_string(),
+                        app.name.to_string(),
+                        app.install_state.to_string(),
+                    ]
+                })
+            })
+            .collect()
+    }
+}
+
+/// Writes the tool's display name followed by its internal name and version when they
+/// differ from the display name
+fn write_tool_name(f: &mut Formatter, name: &str, tool: Option<&InstalledTool>) -> fmt::Result {
+    let Some(tool) = tool else {
+        return writeln!(f, "{} (Not Installed)", name);
+    };
+
+    let details: Vec<&str> = [Some(name), tool.version.as_deref()]
+        .into_iter()
+        .flatten()
+        .filter(|detail| *detail != tool.display_name)
+        .collect();
+    if details.is_empty() {
+        writeln!(f, "{}", tool.display_name)
+    } else {
+        writeln!(f, "{} ({})", tool.display_name, details.join(", "))
+    }
+}
+
+#[derive(Serialize)]
+pub struct DefaultTool {
+    /// Name used in Steam's config
+    pub name: String,
+    /// `None` if the tool isn't installed
+    pub tool: Option<InstalledTool>,
+}
+
+#[derive(Serialize)]
+pub struct MappedTool {
+    /// `None` if the tool is no longer installed
+    pub tool: Option<InstalledTool>,
+    pub apps: Vec<App>,
+}
+
+#[derive(Serialize)]
+pub struct App {
+    pub id: AppId,
+    pub name: String,
+    pub install_state: InstallState,
+    /// Only known for apps with a manifest in a library folder
+    pub installation: Option<Installation>,
+}
+
+impl Display for App {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let update_required = self
+            .installation
+            .as_ref()
+            .is_some_and(|i| i.state_flags.update_required());
+        if self.install_state != InstallState::Installed {
+            writeln!(f, "{} ({})", self.name, self.install_state)?;
+        } else if update_required {
+            writeln!(f, "{} (Update Required)", self.name)?;
+        } else {
+            writeln!(f, "{}", self.name)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+pub struct Installation {
+    pub library_path: PathBuf,
+    /// Size on disk in bytes
+    pub size_on_disk: u64,
+    pub state_flags: StateFlags,
+}
+
+#[derive(Serialize)]
+pub struct LaunchOptions {
+    #[serde(flatten)]
+    pub app: App,
+    #[serde(rename = "launch_options")]
+    pub value: String,
+    /// Only when asked for and the options could be parsed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parsed: Option<ParsedLaunchOptions>,
+}
+
+#[derive(Serialize)]
+pub struct UserLaunchOptions {
+    pub user_id: SteamId64,
+    /// Persona name, or the user id if unknown
+    pub name: String,
+    pub apps: Vec<LaunchOptions>,
+}
+
+#[derive(Serialize)]
+#[serde(transparent)]
+pub struct LaunchOptionsConfig {
+    users: Vec<UserLaunchOptions>,
+    /// Whether each app's launch options were parsed
+    #[serde(skip)]
+    parsed: bool,
+}
+
+/// Writes the parts of parsed launch options, one per line
+fn write_parsed(f: &mut Formatter, parsed: &ParsedLaunchOptions, indent: usize) -> fmt::Result {
+    let env: Vec<String> = parsed
+        .env
+        .iter()
+        .map(|var| format!("{}={}", var.name, quote(&var.value)))
+        .collect();
+    let wrappers: Vec<String> = parsed.wrappers.iter().map(format_wrapper).collect();
+    let args: Vec<Cow<str>> = parsed.args.iter().map(|arg| quote(arg)).collect();
+    let parts = [
+        ("Environment", env.join(" ")),
+        ("Wrappers", wrappers.join("; ")),
+        ("Arguments", args.join(" ")),
+    ];
+
+    for (label, value) in parts.iter().filter(|(_, value)| !value.is_empty()) {
+        writeln!(f, "{:i$}{}: {}", "", label, value, i = indent)?;
+    }
+    if !parsed.command {
+        writeln!(f, "{:i$}No {}", "", launch_options::COMMAND, i = indent)?;
+    }
+
+    Ok(())
+}
+
+/// `NAME` or `NAME=VALUE`
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnvVarFilter {
+    pub name: String,
+    pub value: Option<String>,
+}
+
+impl FromStr for EnvVarFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (name, value) = match s.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (s, None),
+        };
+        if name.is_empty() {
+            return Err("Missing variable name".to_string());
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            value,
+        })
+    }
+}
+
+/// Limits launch options to the apps that set a variable and use a wrapper. Matches every
+/// app by default.
+#[derive(Clone, Debug, Default)]
+pub struct LaunchOptionsFilter {
+    pub env: Option<EnvVarFilter>,
+    /// Matched against the wrapper's file name, so `gamemoderun` matches `/usr/bin/gamemoderun`
+    pub wrapper: Option<String>,
+}
+
+impl LaunchOptionsFilter {
+    fn is_empty(&self) -> bool {
+        self.env.is_none() && self.wrapper.is_none()
+    }
+
+    fn matches(&self, parsed: &ParsedLaunchOptions) -> bool {
+        let env = self.env.as_ref().is_none_or(|filter| {
+            parsed.env.iter().any(|var| {
+                var.name == filter.name && filter.value.as_ref().is_none_or(|v| v == &var.value)
+            })
+        });
+        let wrapper = self.wrapper.as_ref().is_none_or(|name| {
+            parsed
+                .wrappers
+                .iter()
+                .any(|w| w.command.rsplit('/').next() == Some(name.as_str()))
+        });
+
+        env && wrapper
+    }
+}
+
+fn format_wrapper(wrapper: &Wrapper) -> String {
+    std::iter::once(&wrapper.command)
+        .chain(&wrapper.args)
+        .map(|word| quote(word))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl Display for LaunchOptionsConfig {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for user in self.users.iter() {
+            let mut indent = 0;
+            if self.users.len() != 1 {
+                writeln!(f, "{}", user.name)?;
+                indent += 1;
+            }
+
+            let apps = &user.apps;
+            for (i, options) in apps.iter().enumerate() {
+                write!(f, "{:i$}", "", i = indent * INDENT_WIDTH)?;
+                options.app.fmt(f)?;
+                writeln!(
+                    f,
+                    "{:i$}{}",
+                    "",
+                    options.value,
+                    i = (indent + 1) * INDENT_WIDTH
+                )?;
+                if let Some(parsed) = &options.parsed {
+                    write_parsed(f, parsed, (indent + 1) * INDENT_WIDTH)?;
+                }
+
+                if i < apps.len() - 1 {
+                    writeln!(f)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+const LAUNCH_OPTIONS_HEADERS: [&str; 4] = ["user", "app_id", "app_name", "launch_options"];
+const PARSED_LAUNCH_OPTIONS_HEADERS: [&str; 8] = [
+    "user",
+    "app_id",
+    "app_name",
+    "launch_options",
+    "env",
+    "wrappers",
+    "command",
+    "args",
+];
+
+impl Rows for LaunchOptionsConfig {
+    fn headers(&self) -> &'static [&'static str] {
+        if self.parsed {
+            &PARSED_LAUNCH_OPTIONS_HEADERS
+        } else {
+            &LAUNCH_OPTIONS_HEADERS
+        }
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.users
+            .iter()
+            .flat_map(|user| {
+                user.apps.iter().map(|options| {
+                    let mut row = vec![
+                        user.name.to_string(),
+                        options.app.id.to_string(),
+                        options.app.name.to_string(),
+                        options.value.to_string(),
+                    ];
+                    if self.parsed {
+                        let parsed = options.parsed.as_ref();
+                        let quoted = |words: &[String]| {
+                            words.iter().map(|w| quote(w)).collect::<Vec<_>>().join(" ")
+                        };
+                        row.extend([
+                            parsed
+                                .map(|p| {
+                                    p.env
+                                        .iter()
+                                        .map(|var| format!("{}={}", var.name, quote(&var.value)))
+                                        .collect::<Vec<_>>()
+                                        .join(" ")
+                                })
+                                .unwrap_or_default(),
+                            parsed
+                                .map(|p| {
+                                    p.wrappers
+                                        .iter()
+                                        .map(format_wrapper)
+                                        .collect::<Vec<_>>()
+                                        .join("; ")
+                                })
+                                .unwrap_or_default(),
+                            parsed.map(|p| p.command.to_string()).unwrap_or_default(),
+                            parsed.map(|p| quoted(&p.args)).unwrap_or_default(),
+                        ]);
+                    }
+
+                    row
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+pub struct CompatTool {
+    pub name: String,
+    pub install_path: PathBuf,
+    /// Size on disk in bytes
+    pub size: u64,
+}
+
+impl Display for CompatTool {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} ({})", self.name, Size(self.size))?;
+        writeln!(
+            f,
+            "{:i$}{}",
+            "",
+            self.install_path.display(),
+            i = INDENT_WIDTH
+        )
+    }
+}
+
+#[derive(Serialize)]
+pub struct UnusedTools(Vec<CompatTool>);
+impl Display for UnusedTools {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return writeln!(f, "No unused compatibility tools found");
+        }
+
+        for (i, tool) in self.0.iter().enumerate() {
+            tool.fmt(f)?;
+
+            if i < self.0.len() - 1 {
+                writeln!(f)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Rows for UnusedTools {
+    fn headers(&self) -> &'static [&'static str] {
+        &["tool", "install_path", "size"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.0
+            .iter()
+            .map(|tool| {
+                vec![
+                    tool.name.to_string(),
+                    tool.install_path.display().to_string(),
+                    tool.size.to_string(),
+                ]
+            })
+            .collect()
+    }
+}
+
+/// An app's compatibility tool before and after an edit
+#[derive(Serialize)]
+pub struct ToolChange {
+    pub app: App,
+    /// `None` if the app didn't have a tool
+    pub from: Option<String>,
+    pub to: String,
+}
+
+impl Display for ToolChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} ({}): {} -> {}",
+            self.app.name,
+            self.app.id,
+            self.from.as_deref().unwrap_or("(none)"),
+            self.to
+        )
+    }
+}
+
+#[derive(Serialize)]
+pub struct Migration {
+    pub from: String,
+    pub to: String,
+    pub changes: Vec<ToolChange>,
+    /// `true` if the changes weren't written
+    pub dry_run: bool,
+}
+
+impl Rows for ToolChange {
+    fn headers(&self) -> &'static [&'static str] {
+        &["app_id", "app_name", "from", "to"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![tool_change_row(self)]
+    }
+}
+
+fn tool_change_row(change: &ToolChange) -> Vec<String> {
+    vec![
+        change.app.id.to_string(),
+        change.app.name.to_string(),
+        change.from.clone().unwrap_or_default(),
+        change.to.to_string(),
+    ]
+}
+
+impl Display for Migration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.changes.is_empty() {
+            return writeln!(f, "No apps to migrate from {}", self.from);
+        }
+
+        for change in &self.changes {
+            change.fmt(f)?;
+        }
+        writeln!(f)?;
+
+        if self.dry_run {
+            writeln!(
+                f,
+                "{} app(s) would be migrated from {} to {}. No changes were made.",
+                self.changes.len(),
+                self.from,
+                self.to
+            )
+        } else {
+            writeln!(
+                f,
+                "Migrated {} app(s) from {} to {}",
+                self.changes.len(),
+                self.from,
+                self.to
+            )
+        }
+    }
+}
+
+impl Rows for Migration {
+    fn headers(&self) -> &'static [&'static str] {
+        &["app_id", "app_name", "from", "to", "dry_run"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.changes
+            .iter()
+            .map(|change| {
+                let mut row = tool_change_row(change);
+                row.push(self.dry_run.to_string());
+                row
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+pub struct User {
+    pub id: SteamId64,
+    pub name: String,
+}
+
+#[derive(Serialize)]
+pub struct SteamStatus {
+    pub running: bool,
+    pub pid: Option<u32>,
+    /// The logged in user while Steam is running, otherwise the last one to log in
+    pub user: Option<User>,
+}
+
+impl Display for SteamStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.pid {
+            Some(pid) => writeln!(f, "Steam is running (pid {})", pid)?,
+            None => writeln!(f, "Steam isn't running")?,
+        }
+
+        match (&self.user, self.running) {
+            (Some(user), true) => writeln!(f, "Logged in as {} ({})", user.name, user.id),
+            (Some(user), false) => writeln!(f, "Last logged in as {} ({})", user.name, user.id),
+            (None, _) => writeln!(f, "No user is logged in"),
+        }
+    }
+}
+
+impl Rows for SteamStatus {
+    fn headers(&self) -> &'static [&'static str] {
+        &["running", "pid", "user_id", "user_name"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![vec![
+            self.running.to_string(),
+            self.pid.map(|pid| pid.to_string()).unwrap_or_default(),
+            self.user
+                .as_ref()
+                .map(|u| u.id.to_string())
+                .unwrap_or_default(),
+            self.user
+                .as_ref()
+                .map(|u| u.name.to_string())
+                .unwrap_or_default(),
+        ]]
+    }
+}
+
+/// Why an app uses its effective tool
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolReason {
+    /// Selected in the app's properties
+    UserOverride,
+    /// Picked by Valve in the Steam Play manifest
+    ValveDefault,
+    /// The tool for all other titles
+    GlobalDefault,
+    /// The app runs natively on Linux
+    Native,
+    /// A Windows-only app without a tool
+    NoTool,
+}
+
+impl Display for ToolReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ToolReason::UserOverride => write!(f, "user override"),
+            ToolReason::ValveDefault => write!(f, "Valve default"),
+            ToolReason::GlobalDefault => write!(f, "global default"),
+            ToolReason::Native => write!(f, "native"),
+            ToolReason::NoTool => write!(f, "no tool"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct EffectiveTool {
+    #[serde(flatten)]
+    pub app: App,
+    /// Internal tool name. `None` for native apps.
+    pub tool: Option<String>,
+    /// Tool's display name, if it's installed
+    pub display_name: Option<String>,
+    pub reason: ToolReason,
+}
+
+#[derive(Serialize)]
+pub struct EffectiveTools(Vec<EffectiveTool>);
+
+impl Display for EffectiveTools {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for effective in &self.0 {
+            write!(f, "{} ({}): ", effective.app.name, effective.app.id)?;
+            match (&effective.display_name, &effective.tool) {
+                (Some(display_name), _) => writeln!(f, "{} ({})", display_name, effective.reason)?,
+                (None, Some(tool)) => {
+                    writeln!(f, "{} (Not Installed, {})", tool, effective.reason)?
+                }
+                (None, None) => writeln!(f, "{}", effective.reason)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Rows for EffectiveTools {
+    fn headers(&self) -> &'static [&'static str] {
+        &["app_id", "app_name", "tool", "reason"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.0
+            .iter()
+            .map(|effective| {
+                vec![
+                    effective.app.id.to_string(),
+                    effective.app.name.to_string(),
+                    effective.tool.clone().unwrap_or_default(),
+                    effective.reason.to_string(),
+                ]
+            })
+            .collect()
+    }
+}
+
+/// An app Valve picked a tool for
+#[derive(Serialize)]
+pub struct PinnedApp {
+    #[serde(flatten)]
+    pub app: App,
+    pub config: Option<String>,
+    /// Valve's reason for picking the tool
+    pub comment: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PinnedTool {
+    /// `None` if the tool isn't installed
+    pub tool: Option<InstalledTool>,
+    /// Name from the Steam Play manifest
+    pub display_name: Option<String>,
+    pub apps: Vec<PinnedApp>,
+}
+
+#[derive(Serialize)]
+pub struct ValveDefaults(BTreeMap<String, PinnedTool>);
+
+impl Display for ValveDefaults {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, (name, pinned)) in self.0.iter().enumerate() {
+            match (&pinned.tool, &pinned.display_name) {
+                (None, Some(display_name)) => {
+                    writeln!(f, "{} ({}, Not Installed)", display_name, name)?
+                }
+                (tool, _) => write_tool_name(f, name, tool.as_ref())?,
+            }
+
+            for app in &pinned.apps {
+                write!(f, "{:i$}", "", i = INDENT_WIDTH)?;
+                app.app.fmt(f)?;
+                if let Some(comment) = &app.comment {
+                    writeln!(f, "{:i$}{}", "", comment, i = 2 * INDENT_WIDTH)?;
+                }
+            }
+
+            if i < self.0.len() - 1 {
+                writeln!(f)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Rows for ValveDefaults {
+    fn headers(&self) -> &'static [&'static str] {
+        &[
+            "tool",
+            "app_id",
+            "app_name",
+            "install_state",
+            "config",
+            "comment",
+        ]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.0
+            .iter()
+            .flat_map(|(tool, pinned)| {
+                pinned.apps.iter().map(move |app| {
+                    vec![
+                        tool.to_string(),
+                        app.app.id.to_string(),
+                        app.app.name.to_string(),
+                        app.app.install_state.to_string(),
+                        app.config.clone().unwrap_or_default(),
+                        app.comment.clone().unwrap_or_default(),
+                    ]
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+pub struct Prefix {
+    #[serde(flatten)]
+    pub app: App,
+    pub path: PathBuf,
+    /// Contents of the prefix's `version` file
+    pub version: Option<String>,
+    /// Size in bytes
+    pub size: u64,
+    /// Unix timestamp of when the prefix was last used
+    pub modified: Option<u64>,
+    /// Tool the app runs with
+    pub tool: Option<String>,
+    /// Tool's display name, if it's installed
+    pub display_name: Option<String>,
+    pub reason: ToolReason,
+    /// Prefix version the tool writes. `None` if it isn't installed or doesn't say.
+    pub expected_version: Option<String>,
+    /// Whether the prefix version is the expected one. `None` if either is unknown.
+    pub matches_tool: Option<bool>,
+    /// Family of the tool that last upgraded the prefix. `None` if either is unknown.
+    pub family: Option<ToolFamily>,
+    pub warnings: Vec<PrefixWarning>,
+}
+
+#[derive(Serialize)]
+pub struct Prefixes(Vec<Prefix>);
+
+impl Display for Prefixes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, prefix) in self.0.iter().enumerate() {
+            write!(
+                f,
+                "{} ({}): {}, {}",
+                prefix.app.name,
+                prefix.app.id,
+                prefix.version.as_deref().unwrap_or("Unknown Version"),
+                Size(prefix.size)
+            )?;
+            match prefix.modified {
+                Some(modified) => writeln!(f, ", last used {}", Date(modified))?,
+                None => writeln!(f)?,
+            }
+
+            write!(f, "{:i$}", "", i = INDENT_WIDTH)?;
+            let tool = prefix.display_name.as_ref().or(prefix.tool.as_ref());
+            match (tool, prefix.matches_tool) {
+                (None, _) => writeln!(f, "{}", prefix.reason)?,
+                (Some(tool), _) if prefix.display_name.is_none() => {
+                    writeln!(f, "{} ({}, Not Installed)", tool, prefix.reason)?
+                }
+                (Some(tool), Some(true)) => {
+                    writeln!(f, "{} ({}), up to date", tool, prefix.reason)?
+                }
+                (Some(tool), Some(false)) => writeln!(
+                    f,
+                    "{} ({}), expects {}",
+                    tool,
+                    prefix.reason,
+                    prefix.expected_version.as_deref().unwrap_or_default()
+                )?,
+                (Some(tool), None) => writeln!(f, "{} ({})", tool, prefix.reason)?,
+            }
+
+            for warning in &prefix.warnings {
+                let tool = prefix.display_name.as_deref().unwrap_or_default();
+                write!(f, "{:i$}Warning: ", "", i = INDENT_WIDTH)?;
+                match warning {
+                    PrefixWarning::ToolOlder => {
+                        writeln!(f, "{} is older than the prefix, which may break it", tool)?
+                    }
+                    PrefixWarning::DifferentFamily => writeln!(
+                        f,
+                        "prefix was created by {}, but the app runs with {}",
+                        prefix.family.unwrap_or(ToolFamily::Other),
+                        tool
+                    )?,
+                }
+            }
+
+            if i < self.0.len() - 1 {
+                writeln!(f)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Rows for Prefixes {
+    fn headers(&self) -> &'static [&'static str] {
+        &[
+            "app_id",
+            "app_name",
+            "path",
+            "version",
+            "size",
+            "modified",
+            "tool",
+            "reason",
+            "matches_tool",
+            "family",
+            "warnings",
+        ]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.0
+            .iter()
+            .map(|prefix| {
+                vec![
+                    prefix.app.id.to_string(),
+                    prefix.app.name.to_string(),
+                    prefix.path.to_string_lossy().into_owned(),
+                    prefix.version.clone().unwrap_or_default(),
+                    prefix.size.to_string(),
+                    prefix.modified.map(|m| m.to_string()).unwrap_or_default(),
+                    prefix.tool.clone().unwrap_or_default(),
+                    prefix.reason.to_string(),
+                    prefix
+                        .matches_tool
+                        .map(|m| m.to_string())
+                        .unwrap_or_default(),
+                    prefix.family.map(|f| f.to_string()).unwrap_or_default(),
+                    prefix
+                        .warnings
+                        .iter()
+                        .map(|w| w.to_string())
+                        .collect::<Vec<_>>()
+                        .join(";"),
+                ]
+            })
+            .collect()
+    }
+}
+
+/// Serialized as the directory name under `steamapps`
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrphanKind {
+    /// A Wine prefix in `steamapps/compatdata`
+    CompatData,
+    /// Compiled shaders in `steamapps/shadercache`
+    ShaderCache,
+}
+
+impl Display for OrphanKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            OrphanKind::CompatData => write!(f, "compatdata"),
+            OrphanKind::ShaderCache => write!(f, "shadercache"),
+        }
+    }
+}
+
+/// A directory left behind by an app that's no longer installed
+#[derive(Serialize)]
+pub struct OrphanDir {
+    pub app_id: AppId,
+    /// `None` if Steam no longer knows the app's name
+    pub name: Option<String>,
+    pub kind: OrphanKind,
+    pub path: PathBuf,
+    /// Size in bytes
+    pub size: u64,
+    pub deleted: bool,
+    /// Why the directory couldn't be deleted
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OrphanAction {
+    List,
+    DryRun,
+    Delete,
+}
+
+#[derive(Serialize)]
+pub struct Orphans {
+    pub dirs: Vec<OrphanDir>,
+    #[serde(skip)]
+    action: OrphanAction,
+}
+
+impl Orphans {
+    /// Directories that couldn't be deleted
+    pub fn failed(&self) -> usize {
+        self.dirs.iter().filter(|dir| dir.error.is_some()).count()
+    }
+}
+
+impl Display for Orphans {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.dirs.is_empty() {
+            return writeln!(f, "No orphaned directories");
+        }
+
+        for dir in &self.dirs {
+            write!(f, "{} ({}", dir.path.display(), dir.app_id)?;
+            if let Some(name) = &dir.name {
+                write!(f, ", {}", name)?;
+            }
+            write!(f, "): {}", Size(dir.size))?;
+            match &dir.error {
+                Some(e) => writeln!(f, ", couldn't delete: {}", e)?,
+                None => writeln!(f)?,
+            }
+        }
+        writeln!(f)?;
+
+        let count = self.dirs.len();
+        let size = Size(self.dirs.iter().map(|dir| dir.size).sum());
+        match self.action {
+            OrphanAction::List => writeln!(
+                f,
+                "{} orphaned directories ({}). Use --delete to remove them.",
+                count, size
+            ),
+            OrphanAction::DryRun => writeln!(
+                f,
+                "{} directories ({}) would be deleted. Nothing was deleted.",
+                count, size
+            ),
+            OrphanAction::Delete => {
+                let deleted: Vec<&OrphanDir> = self.dirs.iter().filter(|dir| dir.deleted).collect();
+                let size = Size(deleted.iter().map(|dir| dir.size).sum());
+                writeln!(f, "Deleted {} directories ({})", deleted.len(), size)?;
+                match self.failed() {
+                    0 => Ok(()),
+                    failed => writeln!(f, "Couldn't delete {} directories", failed),
+                }
+            }
+        }
+    }
+}
+
+impl Rows for Orphans {
+    fn headers(&self) -> &'static [&'static str] {
+        &[
+            "app_id", "app_name", "kind", "path", "size", "deleted", "error",
+        ]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.dirs
+            .iter()
+            .map(|dir| {
+                vec![
+                    dir.app_id.to_string(),
+                    dir.name.clone().unwrap_or_default(),
+                    dir.kind.to_string(),
+                    dir.path.to_string_lossy().into_owned(),
+                    dir.size.to_string(),
+                    dir.deleted.to_string(),
+                    dir.error.clone().unwrap_or_default(),
+                ]
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+pub struct Backups(Vec<Backup>);
+
+impl Backups {
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Display for Backups {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return writeln!(f, "No backups");
+        }
+
+        for backup in &self.0 {
+            writeln!(
+                f,
+                "{}  {} ({})",
+                backup.id,
+                backup.source.display(),
+                Size(backup.size)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+const BACKUP_HEADERS: &[&str] = &["id", "source", "size"];
+
+fn backup_row(backup: &Backup) -> Vec<String> {
+    vec![
+        backup.id.to_string(),
+        backup.source.to_string_lossy().into_owned(),
+        backup.size.to_string(),
+    ]
+}
+
+impl Rows for Backups {
+    fn headers(&self) -> &'static [&'static str] {
+        BACKUP_HEADERS
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.0.iter().map(backup_row).collect()
+    }
+}
+
+/// A backup that was copied back over the file it was taken from
+#[derive(Serialize)]
+#[serde(transparent)]
+pub struct RestoredBackup(pub Backup);
+
+impl Display for RestoredBackup {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Restored '{}' from {}",
+            self.0.source.display(),
+            self.0.id
+        )
+    }
+}
+
+impl Rows for RestoredBackup {
+    fn headers(&self) -> &'static [&'static str] {
+        BACKUP_HEADERS
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![backup_row(&self.0)]
+    }
+}
+
+/// Backups removed by `backup prune`
+#[derive(Serialize)]
+#[serde(transparent)]
+pub struct PrunedBackups(Vec<Backup>);
+
+impl Display for PrunedBackups {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Removed {} backup(s)", self.0.len())
+    }
+}
+
+impl Rows for PrunedBackups {
+    fn headers(&self) -> &'static [&'static str] {
+        BACKUP_HEADERS
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.0.iter().map(backup_row).collect()
+    }
+}
+
+#[derive(Serialize)]
+pub struct InstallReport<T> {
+    pub install: SteamInstall,
+    pub report: T,
+}
+
+#[derive(Serialize)]
+pub struct ToolLocation {
+    /// Name of the installation it was found through
+    pub install: String,
+    pub install_path: PathBuf,
+}
+
+/// The same report for several Steam installations
+#[derive(Serialize)]
+pub struct AllInstalls<T> {
+    pub installs: Vec<InstallReport<T>>,
+    /// Tools installed in more than one place, by name
+    pub duplicate_tools: BTreeMap<String, Vec<ToolLocation>>,
+}
+
+impl<T: Display> Display for AllInstalls<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, report) in self.installs.iter().enumerate() {
+            if i > 0 {
+                write!(f, "\n\n")?;
+            }
+            writeln!(
+                f,
+                "[{}] {}",
+                report.install.name,
+                report.install.root.display()
+            )?;
+            // Reports may or may not end with a line break
+            write!(f, "{}", report.report.to_string().trim_end())?;
+        }
+
+        if !self.duplicate_tools.is_empty() {
+            write!(f, "\n\nInstalled in more than one place:")?;
+            for (name, locations) in &self.duplicate_tools {
+                write!(f, "\n{}", name)?;
+                for location in locations {
+                    write!(
+                        f,
+                        "\n{:i$}{}: {}",
+                        "",
+                        location.install,
+                        location.install_path.display(),
+                        i = INDENT_WIDTH
+                    )?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: Rows> AllInstalls<T> {
+    /// Rows of every installation, prefixed with its name
+    fn install_rows(&self) -> Vec<Vec<String>> {
+        self.installs
+            .iter()
+            .flat_map(|report| {
+                report.report.rows().into_iter().map(|row| {
+                    std::iter::once(report.install.name.to_string())
+                        .chain(row)
+                        .collect()
+                })
+            })
+            .collect()
+    }
+}
+
+/// `headers` with an `installation` column first. `M` must be one more than `N`.
+const fn with_installation<const N: usize, const M: usize>(
+    headers: [&'static str; N],
+) -> [&'static str; M] {
+    assert!(M == N + 1);
+    let mut result = ["installation"; M];
+    let mut i = 0;
+    while i < N {
+        result[i + 1] = headers[i];
+        i += 1;
+    }
+
+    result
+}
+
+impl Rows for AllInstalls<CompatToolConfig> {
+    fn headers(&self) -> &'static [&'static str] {
+        const HEADERS: [&str; 5] = with_installation(COMPAT_TOOL_HEADERS);
+        &HEADERS
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.install_rows()
+    }
+}
+
+impl Rows for AllInstalls<LaunchOptionsConfig> {
+    fn headers(&self) -> &'static [&'static str] {
+        const HEADERS: [&str; 5] = with_installation(LAUNCH_OPTIONS_HEADERS);
+        const PARSED_HEADERS: [&str; 9] = with_installation(PARSED_LAUNCH_OPTIONS_HEADERS);
+        if self.installs.iter().any(|report| report.report.parsed) {
+            &PARSED_HEADERS
+        } else {
+            &HEADERS
+        }
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.install_rows()
+    }
+}
+
+/// Serialized as lowercase
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OptionKind {
+    /// An environment variable
+    Env,
+    Wrapper,
+}
+
+impl Display for OptionKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            OptionKind::Env => write!(f, "env"),
+            OptionKind::Wrapper => write!(f, "wrapper"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct OptionUsage {
+    pub kind: OptionKind,
+    /// Variable name, or the wrapper's file name
+    pub name: String,
+    /// Number of apps using it, counted once per user
+    pub apps: usize,
+}
+
+/// How often each environment variable and wrapper is used, most used first
+#[derive(Serialize)]
+pub struct LaunchOptionsStats(Vec<OptionUsage>);
+
+impl Display for LaunchOptionsStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let sections = [
+            (OptionKind::Env, "Environment Variables"),
+            (OptionKind::Wrapper, "Wrappers"),
+        ];
+        let mut first = true;
+        for (kind, title) in sections {
+            let usages: Vec<&OptionUsage> = self.0.iter().filter(|u| u.kind == kind).collect();
+            if usages.is_empty() {
+                continue;
+            }
+            if !first {
+                writeln!(f)?;
+            }
+            first = false;
+
+            let width = usages
+                .iter()
+                .map(|u| u.name.len())
+                .max()
+                .unwrap_or_default();
+            writeln!(f, "{}", title)?;
+            for usage in usages {
+                writeln!(
+                    f,
+                    "{:i$}{:w$}  {}",
+                    "",
+                    usage.name,
+                    usage.apps,
+                    i = INDENT_WIDTH,
+                    w = width
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Rows for LaunchOptionsStats {
+    fn headers(&self) -> &'static [&'static str] {
+        &["kind", "name", "apps"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.0
+            .iter()
+            .map(|usage| {
+                vec![
+                    usage.kind.to_string(),
+                    usage.name.to_string(),
+                    usage.apps.to_string(),
+                ]
+            })
+            .collect()
+    }
+}
+
+#[derive(Serialize)]
+pub struct LintProblem {
+    pub severity: Severity,
+    /// Name of the check that found it
+    pub check: &'static str,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+pub struct AppLints {
+    #[serde(flatten)]
+    pub app: App,
+    pub launch_options: String,
+    pub problems: Vec<LintProblem>,
+}
+
+#[derive(Serialize)]
+pub struct UserLints {
+    pub user_id: SteamId64,
+    /// Persona name, or the user id if unknown
+    pub name: String,
+    pub apps: Vec<AppLints>,
+}
+
+/// Apps whose launch options have problems, by user
+#[derive(Serialize)]
+pub struct LintReport(Vec<UserLints>);
+
+impl LintReport {
+    fn count(&self, severity: Severity) -> usize {
+        self.0
+            .iter()
+            .flat_map(|user| &user.apps)
+            .flat_map(|app| &app.problems)
+            .filter(|problem| problem.severity == severity)
+            .count()
+    }
+
+    pub fn errors(&self) -> usize {
+        self.count(Severity::Error)
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.count(Severity::Warning)
+    }
+}
+
+impl Display for LintReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            return writeln!(f, "No problems found");
+        }
+
+        for user in &self.0 {
+            writeln!(f, "{}", user.name)?;
+            for app in &user.apps {
+                let indent = INDENT_WIDTH;
+                writeln!(f, "{:i$}{} ({})", "", app.app.name, app.app.id, i = indent)?;
+                writeln!(f, "{:i$}{}", "", app.launch_options, i = indent * 2)?;
+                for problem in &app.problems {
+                    writeln!(
+                        f,
+                        "{:i$}{}: {}",
+                        "",
+                        problem.severity,
+                        problem.message,
+                        i = indent * 2
+                    )?;
+                }
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(
+            f,
+            "{} error(s), {} warning(s)",
+            self.errors(),
+            self.warnings()
+        )
+    }
+}
+
+/// A change to an app's launch options
+pub enum LaunchOptionsEdit {
+    /// Replaces the options
+    Set(String),
+    /// Sets an environment variable, replacing any existing value
+    AddEnv(EnvVar),
+    /// Removes an environment variable
+    RemoveEnv(String),
+    /// Removes all options
+    Clear,
+}
+
+/// An app's launch options before and after an edit
+#[derive(Serialize)]
+pub struct LaunchOptionsChange {
+    pub user: User,
+    pub app: App,
+    /// Empty if the app didn't have launch options
+    pub from: String,
+    pub to: String,
+}
+
+/// Writes an app's launch options before and after a change, like a diff
+fn write_change(f: &mut Formatter, change: &LaunchOptionsChange, indent: usize) -> fmt::Result {
+    let options = |options: &str| {
+        if options.is_empty() {
+            "(none)".to_string()
+        } else {
+            options.to_string()
+        }
+    };
+
+    write!(
+        f,
+        "{:i$}{} ({})",
+        "",
+        change.app.name,
+        change.app.id,
+        i = indent
+    )?;
+    if change.from == change.to {
+        return writeln!(f, ": unchanged");
+    }
+    writeln!(f)?;
+    let indent = indent + INDENT_WIDTH;
+    writeln!(f, "{:i$}- {}", "", options(&change.from), i = indent)?;
+    writeln!(f, "{:i$}+ {}", "", options(&change.to), i = indent)
+}
+
+impl Display for LaunchOptionsChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write_change(f, self, 0)
+    }
+}
+
+fn launch_options_change_row(change: &LaunchOptionsChange) -> Vec<String> {
+    vec![
+        change.user.name.to_string(),
+        change.app.id.to_string(),
+        change.app.name.to_string(),
+        change.from.to_string(),
+        change.to.to_string(),
+    ]
+}
+
+impl Rows for LaunchOptionsChange {
+    fn headers(&self) -> &'static [&'static str] {
+        &["user", "app_id", "app_name", "from", "to"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        vec![launch_options_change_row(self)]
+    }
+}
+
+/// How `launch-options replace` rewrites launch options
+pub enum ReplaceAction {
+    /// Renames an environment variable, keeping its value
+    Env { from: String, to: String },
+    /// Replaces every match of a regular expression. `$1` or `${name}` in the replacement
+    /// insert capture groups.
+    Regex { pattern: Regex, replacement: String },
+}
+
+impl ReplaceAction {
+    /// Errors if `to` isn't a valid variable name or is the same as `from`
+    pub fn env(from: &str, to: &str) -> Result<Self> {
+        if launch_options::parse_assignment(&format!("{}=", to)).is_none() {
+            return Err(format!("'{}' isn't a valid variable name", to).into());
+        }
+        if from == to {
+            return Err(format!("Can't rename {} to itself", from).into());
+        }
+
+        Ok(ReplaceAction::Env {
+            from: from.to_string(),
+            to: to.to_string(),
+        })
+    }
+
+    pub fn regex(pattern: &str, replacement: &str) -> Result<Self> {
+        Ok(ReplaceAction::Regex {
+            pattern: Regex::new(pattern)?,
+            replacement: replacement.to_string(),
+        })
+    }
+
+    fn apply(&self, options: &str) -> Result<String> {
+        match self {
+            ReplaceAction::Env { from, to } => Ok(launch_options::rename_env(options, from, to)?
+                .unwrap_or_else(|| options.to_string())),
+            ReplaceAction::Regex {
+                pattern,
+                replacement,
+            } => Ok(pattern
+                .replace_all(options, replacement.as_str())
+                .into_owned()),
+        }
+    }
+}
+
+/// Launch options rewritten by `launch-options replace`
+#[derive(Serialize)]
+pub struct Replacement {
+    pub changes: Vec<LaunchOptionsChange>,
+    /// `true` if the changes weren't written
+    pub dry_run: bool,
+}
+
+impl Display for Replacement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.changes.is_empty() {
+            return writeln!(f, "No launch options to change");
+        }
+
+        let mut user = None;
+        for change in &self.changes {
+            if user != Some(&change.user.id) {
+                if user.is_some() {
+                    writeln!(f)?;
+                }
+                writeln!(f, "{}", change.user.name)?;
+                user = Some(&change.user.id);
+            }
+            write_change(f, change, INDENT_WIDTH)?;
+        }
+        writeln!(f)?;
+
+        if self.dry_run {
+            writeln!(
+                f,
+                "Launch options of {} app(s) would be changed. No changes were made.",
+                self.changes.len()
+            )
+        } else {
+            writeln!(f, "Changed launch options of {} app(s)", self.changes.len())
+        }
+    }
+}
+
+impl Rows for Replacement {
+    fn headers(&self) -> &'static [&'static str] {
+        &["user", "app_id", "app_name", "from", "to", "dry_run"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.changes
+            .iter()
+            .map(|change| {
+                let mut row = launch_options_change_row(change);
+                row.push(self.dry_run.to_string());
+                row
+            })
+            .collect()
+    }
+}
+
+impl Rows for LintReport {
+    fn headers(&self) -> &'static [&'static str] {
+        &["user", "app_id", "app_name", "severity", "check", "message"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.0
+            .iter()
+            .flat_map(|user| {
+                user.apps.iter().flat_map(move |app| {
+                    app.problems.iter().map(move |problem| {
+                        vec![
+                            user.name.to_string(),
+                            app.app.id.to_string(),
+                            app.app.name.to_string(),
+                            problem.severity.to_string(),
+                            problem.check.to_string(),
+                            problem.message.to_string(),
+                        ]
+                    })
+                })
+            })
+            .collect()
+    }
+}
+
+/// Human readable byte count
+struct Size(u64);
+impl Display for Size {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+        let mut size = self.0 as f64;
+        let mut unit = 0;
+        while size >= 1024.0 && unit < UNITS.len() - 1 {
+            size /= 1024.0;
+            unit += 1;
+        }
+
+        if unit == 0 {
+            write!(f, "{} {}", self.0, UNITS[unit])
+        } else {
+            write!(f, "{:.1} {}", size, UNITS[unit])
+        }
+    }
+}
+
+/// Unix timestamp shown as a UTC date and time
+struct Date(u64);
+impl Display for Date {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let (year, month, day) = backup::civil_from_days(self.0 / 86400);
+        let secs_of_day = self.0 % 86400;
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+            year,
+            month,
+            day,
+            secs_of_day / 3600,
+            secs_of_day % 3600 / 60
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstallState {
+    NotInstalled,
+    Installed,
+    Shortcut,
+    Unknown,
+}
+
+impl Display for InstallState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InstallState::NotInstalled => write!(f, "Not Installed"),
+            InstallState::Installed => write!(f, "Installed"),
+            InstallState::Shortcut => write!(f, "Shortcut"),
+            InstallState::Unknown => write!(f, "Unknown Install State"),
+        }
+    }
+}
+
+/// Launch options of every user's apps and shortcuts that match `filter`. With `parse`, each is
+/// also split into environment variables, wrappers and arguments.
+pub fn parse_launch_options(
+    steam_home: &Path,
+    parse: bool,
+    filter: &LaunchOptionsFilter,
+) -> Result<LaunchOptionsConfig> {
+    let launch_options = steam::parse_launch_options_mapping(steam_home)?;
+    let user_shortcuts = steam::shortcuts::parse_shortcuts(steam_home)?;
+    let unique_apps = launch_options
+        .values()
+        .flat_map(|ids| ids.iter().map(|o| &o.app_id))
+        .collect();
+    let registry = get_registry(steam_home, &unique_apps)?;
+    let (app_names, shortcuts) = get_app_names(steam_home, &unique_apps, &registry)?;
+    let manifests = steam::library_folders::find_installed_apps(steam_home);
+
+    let mut users: BTreeMap<SteamId64, Vec<LaunchOptions>> = BTreeMap::new();
+    for (id, options) in launch_options {
+        let o = options.iter().map(|l| {
+            let app = to_app(&l.app_id, &app_names, &manifests, &registry, &shortcuts);
+
+            LaunchOptions {
+                app,
+                value: l.options.to_string(),
+                parsed: None,
+            }
+        });
+        users.entry(id).or_default().extend(o);
+    }
+    for (id, shortcuts) in user_shortcuts {
+        let o = shortcuts
+            .into_iter()
+            .filter(|s| !s.launch_options.is_empty())
+            .map(|s| LaunchOptions {
+                app: App {
+                    id: s.app_id,
+                    name: s.name,
+                    install_state: InstallState::Shortcut,
+                    installation: None,
+                },
+                value: s.launch_options,
+                parsed: None,
+            });
+        users.entry(id).or_default().extend(o);
+    }
+
+    let mut result = Vec::new();
+    for (user_id, mut apps) in users {
+        apps.sort_by(|a, b| a.app.name.cmp(&b.app.name));
+        if parse || !filter.is_empty() {
+            for options in &mut apps {
+                options.parsed = launch_options::parse_launch_options(&options.value)
+                    .map_err(|e| {
+                        log::warn!(
+                            "Couldn't parse launch options of {} ({}): {}",
+                            options.app.name,
+                            options.app.id,
+                            e
+                        )
+                    })
+                    .ok();
+            }
+            apps.retain(|options| options.parsed.as_ref().is_some_and(|p| filter.matches(p)));
+            if !parse {
+                apps.iter_mut().for_each(|options| options.parsed = None);
+            }
+        }
+        if apps.is_empty() && !filter.is_empty() {
+            continue;
+        }
+        let name = steam::get_display_name(steam_home, &user_id)?;
+        result.push(UserLaunchOptions {
+            user_id,
+            name,
+            apps,
+        });
+    }
+    result.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(LaunchOptionsConfig {
+        users: result,
+        parsed: parse,
+    })
+}
+
+/// How many apps set each environment variable and use each wrapper in their launch options
+pub fn find_launch_options_stats(steam_home: &Path) -> Result<LaunchOptionsStats> {
+    let config = parse_launch_options(steam_home, true, &LaunchOptionsFilter::default())?;
+    let mut counts: BTreeMap<(OptionKind, String), usize> = BTreeMap::new();
+    for parsed in config
+        .users
+        .iter()
+        .flat_map(|user| &user.apps)
+        .filter_map(|options| options.parsed.as_ref())
+    {
+        // Counted once per app, even if it's set more than once
+        let env = parsed
+            .env
+            .iter()
+            .map(|var| (OptionKind::Env, var.name.to_string()));
+        let wrappers = parsed.wrappers.iter().map(|wrapper| {
+            let name = wrapper.command.rsplit('/').next().unwrap_or_default();
+            (OptionKind::Wrapper, name.to_string())
+        });
+        for key in env.chain(wrappers).collect::<BTreeSet<_>>() {
+            *counts.entry(key).or_default() += 1;
+        }
+    }
+
+    let mut usages: Vec<OptionUsage> = counts
+        .into_iter()
+        .map(|((kind, name), apps)| OptionUsage { kind, name, apps })
+        .collect();
+    usages.sort_by(|a, b| a.kind.cmp(&b.kind).then(b.apps.cmp(&a.apps)));
+
+    Ok(LaunchOptionsStats(usages))
+}
+
+/// Checks every user's launch options for common mistakes
+pub fn lint_launch_options(steam_home: &Path) -> Result<LintReport> {
+    let config = parse_launch_options(steam_home, false, &LaunchOptionsFilter::default())?;
+    let users = config
+        .users
+        .into_iter()
+        .map(|user| {
+            let apps = user
+                .apps
+                .into_iter()
+                .filter_map(|options| {
+                    let shortcut = options.app.install_state == InstallState::Shortcut;
+                    let problems: Vec<LintProblem> =
+                        launch_options::lint_launch_options(&options.value, shortcut)
+                            .into_iter()
+                            .map(|lint| LintProblem {
+                                severity: lint.severity(),
+                                check: lint.check(),
+                                message: lint.to_string(),
+                            })
+                            .collect();
+
+                    (!problems.is_empty()).then_some(AppLints {
+                        app: options.app,
+                        launch_options: options.value,
+                        problems,
+                    })
+                })
+                .collect();
+
+            UserLints {
+                user_id: user.user_id,
+                name: user.name,
+                apps,
+            }
+        })
+        .filter(|user| !user.apps.is_empty())
+        .collect();
+
+    Ok(LintReport(users))
+}
+
+/// Edits the launch options of an app, by id or name, for `user` (id or persona name), or the
+/// most recent user. Shortcuts keep theirs in shortcuts.vdf and can't be edited.
+pub fn edit_launch_options(
+    steam_home: &Path,
+    user: Option<&str>,
+    app: &str,
+    edit: &LaunchOptionsEdit,
+    backups: &BackupStore,
+) -> Result<LaunchOptionsChange> {
+    let user = resolve_user(steam_home, user)?;
+    let manifests = steam::library_folders::find_installed_apps(steam_home);
+    let app_id = resolve_app_id(steam_home, app, &manifests)?;
+    let whitelist = HashSet::from([&app_id]);
+    let registry = get_registry(steam_home, &whitelist)?;
+    let (app_names, shortcuts) = get_app_names(steam_home, &whitelist, &registry)?;
+    if shortcuts.contains_key(&app_id) {
+        return Err(format!(
+            "{} is a shortcut. Its launch options can only be changed in Steam.",
+            app_id
+        )
+        .into());
+    }
+    if !app_names.contains_key(&app_id) && !manifests.contains_key(&app_id) {
+        return Err(format!("App {} doesn't exist", app_id).into());
+    }
+    let app = to_app(&app_id, &app_names, &manifests, &registry, &shortcuts);
+
+    let config_path = steam::local_config_path(steam_home, &user.id);
+    let mut config = Document::parse(read_text_config(&config_path).map_err(|e| {
+        format!(
+            "Couldn't read launch options of {} from '{}': {}",
+            user.name,
+            config_path.display(),
+            e
+        )
+    })?)?;
+    let from = steam::get_launch_options(&config, &app_id)?.unwrap_or_default();
+    let to = match edit {
+        LaunchOptionsEdit::Set(options) => options.to_string(),
+        LaunchOptionsEdit::AddEnv(var) => launch_options::set_env(&from, var)?,
+        LaunchOptionsEdit::RemoveEnv(name) => launch_options::remove_env(&from, name)?
+            .ok_or_else(|| format!("{} ({}) doesn't set {}", app.name, app.id, name))?,
+        LaunchOptionsEdit::Clear => String::new(),
+    };
+
+    if from != to {
+        steam::set_launch_options(&mut config, &app_id, &to)?;
+        write_text_config(&config_path, config.as_str(), backups)?;
+    }
+
+    Ok(LaunchOptionsChange {
+        user,
+        app,
+        from,
+        to,
+    })
+}
+
+/// Rewrites the launch options of every app of `user` (id or persona name, or the most recent
+/// user), or of every user with `all_users`. Shortcuts keep theirs in shortcuts.vdf and are
+/// left alone.
+pub fn replace_launch_options(
+    steam_home: &Path,
+    user: Option<&str>,
+    all_users: bool,
+    action: &ReplaceAction,
+    dry_run: bool,
+    backups: &BackupStore,
+) -> Result<Replacement> {
+    let selected = match all_users {
+        true => None,
+        false => Some(resolve_user(steam_home, user)?.id),
+    };
+
+    let mut edits = Vec::new();
+    for (user_id, apps) in steam::parse_launch_options_mapping(steam_home)? {
+        if selected.as_ref().is_some_and(|id| id != &user_id) {
+            continue;
+        }
+
+        let mut changed = Vec::new();
+        for options in apps {
+            match action.apply(&options.options) {
+                Ok(to) if to != options.options => {
+                    changed.push((options.app_id, options.options, to))
+                }
+                Ok(_) => {}
+                Err(e) => log::warn!(
+                    "Couldn't change launch options of {}: {}",
+                    options.app_id,
+                    e
+                ),
+            }
+        }
+        if changed.is_empty() {
+            continue;
+        }
+
+        if !dry_run {
+            let config_path = steam::local_config_path(steam_home, &user_id);
+            let mut config = Document::parse(read_text_config(&config_path)?)?;
+            for (app_id, _, to) in &changed {
+                steam::set_launch_options(&mut config, app_id, to)?;
+            }
+            write_text_config(&config_path, config.as_str(), backups)?;
+        }
+        edits.push((user_id, changed));
+    }
+
+    let unique_apps = edits
+        .iter()
+        .flat_map(|(_, apps)| apps.iter().map(|(id, _, _)| id))
+        .collect();
+    let registry = get_registry(steam_home, &unique_apps)?;
+    let (app_names, shortcuts) = get_app_names(steam_home, &unique_apps, &registry)?;
+    let manifests = steam::library_folders::find_installed_apps(steam_home);
+
+    let mut changes = Vec::new();
+    for (user_id, apps) in &edits {
+        let name = steam::get_display_name(steam_home, user_id)?;
+        let mut user_changes: Vec<LaunchOptionsChange> = apps
+            .iter()
+            .map(|(app_id, from, to)| LaunchOptionsChange {
+                user: User {
+                    id: *user_id,
+                    name: name.to_string(),
+                },
+                app: to_app(app_id, &app_names, &manifests, &registry, &shortcuts),
+                from: from.to_string(),
+                to: to.to_string(),
+            })
+            .collect();
+        user_changes.sort_by(|a, b| a.app.name.cmp(&b.app.name));
+        changes.extend(user_changes);
+    }
+
+    Ok(Replacement { changes, dry_run })
+}
+
+pub fn parse_tool_mapping(steam_home: &Path) -> Result<CompatToolConfig> {
+    let tool_mapping = get_tool_mapping(steam_home)?;
+    let unique_apps = tool_mapping.apps();
+
+    let registry = get_registry(steam_home, &unique_apps)?;
+    let (app_names, shortcuts) = get_app_names(steam_home, &unique_apps, &registry)?;
+    let manifests = steam::library_folders::find_installed_apps(steam_home);
+    let installed_tools = steam::installed_tools::find_installed_tools(steam_home);
+
+    let default_tool = tool_mapping.default_tool.clone().map(|name| DefaultTool {
+        tool: installed_tools.iter().find(|t| t.name == name).cloned(),
+        name,
+    });
+    let tools = tool_mapping
+        .into_iter()
+        .map(|(name, ids)| {
+            let mut apps: Vec<App> = ids
+                .iter()
+                .map(|id| to_app(id, &app_names, &manifests, &registry, &shortcuts))
+                .collect();
+            apps.sort_by(|a, b| a.name.cmp(&b.name));
+            let tool = installed_tools.iter().find(|t| t.name == name).cloned();
+            if tool.is_none() {
+                log::info!(
+                    "{} is mapped to {} app(s) but isn't installed",
+                    name,
+                    apps.len()
+                );
+            }
+            (name, MappedTool { tool, apps })
+        })
+        .collect();
+
+    Ok(CompatToolConfig {
+        default_tool,
+        tools,
+    })
+}
+
+/// Runs a report against every installation. Installations it fails for are skipped.
+pub fn for_all_installs<T>(
+    installs: Vec<SteamInstall>,
+    report: impl Fn(&Path) -> Result<T>,
+) -> Result<AllInstalls<T>> {
+    let mut reports = Vec::new();
+    let mut locations: BTreeMap<String, Vec<ToolLocation>> = BTreeMap::new();
+    for install in installs {
+        match report(&install.home) {
+            Ok(report) => {
+                for tool in steam::installed_tools::find_installed_tools(&install.home) {
+                    let found = locations.entry(tool.name).or_default();
+                    if !found.iter().any(|l| l.install_path == tool.install_path) {
+                        found.push(ToolLocation {
+                            install: install.name.to_string(),
+                            install_path: tool.install_path,
+                        });
+                    }
+                }
+                reports.push(InstallReport { install, report });
+            }
+            Err(e) => log::warn!(
+                "Skipping {} Steam in '{}': {}",
+                install.name,
+                install.root.display(),
+                e
+            ),
+        }
+    }
+    if reports.is_empty() {
+        return Err("Couldn't read any Steam installation".into());
+    }
+    locations.retain(|_, found| found.len() > 1);
+
+    Ok(AllInstalls {
+        installs: reports,
+        duplicate_tools: locations,
+    })
+}
+
+/// The tool each installed game runs with
+pub fn find_effective_tools(steam_home: &Path) -> Result<EffectiveTools> {
+    let manifests = steam::library_folders::find_installed_apps(steam_home);
+    let resolver = ToolResolver::load(steam_home, |id| manifests.contains_key(id))?;
+    let installed_tools = steam::installed_tools::find_installed_tools(steam_home);
+    let whitelist = manifests.keys().collect();
+    let registry = get_registry(steam_home, &whitelist)?;
+    let app_names = resolver.app_names(&registry);
+
+    let mut tools: Vec<EffectiveTool> = manifests
+        .keys()
+        .filter(|id| !resolver.is_tool(id))
+        .map(|id| {
+            let (tool, reason) = resolver.resolve(id);
+            EffectiveTool {
+                app: to_app(id, &app_names, &manifests, &registry, &HashMap::new()),
+                display_name: tool.and_then(|tool| {
+                    installed_tools
+                        .iter()
+                        .find(|t| t.name == tool)
+                        .map(|t| t.display_name.to_string())
+                }),
+                tool: tool.map(|tool| tool.to_string()),
+                reason,
+            }
+        })
+        .collect();
+    tools.sort_by(|a, b| a.app.name.cmp(&b.app.name));
+
+    Ok(EffectiveTools(tools))
+}
+
+/// Every prefix in `steamapps/compatdata` of each library, along with the tool its app runs with
+pub fn find_prefixes(steam_home: &Path) -> Result<Prefixes> {
+    let compat_data: Vec<CompatData> = steam::library_folders::find_libraries(steam_home)
+        .iter()
+        .flat_map(|library| steam::compat_data::find_compat_data(library))
+        .filter(|data| data.app_id != steam::DEFAULT_PROTON_APP_ID)
+        .collect();
+    let whitelist: HashSet<&AppId> = compat_data.iter().map(|data| &data.app_id).collect();
+    let resolver = ToolResolver::load(steam_home, |id| whitelist.contains(id))?;
+    let installed_tools = steam::installed_tools::find_installed_tools(steam_home);
+    let manifests = steam::library_folders::find_installed_apps(steam_home);
+    let registry = get_registry(steam_home, &whitelist)?;
+    let (app_names, shortcuts) = get_app_names(steam_home, &whitelist, &registry)?;
+
+    let mut prefixes: Vec<Prefix> = compat_data
+        .into_iter()
+        .map(|data| {
+            let (tool, reason) = resolver.resolve(&data.app_id);
+            let installed_tool =
+                tool.and_then(|tool| installed_tools.iter().find(|t| t.name == tool));
+            let expected_version = installed_tool.and_then(|t| t.prefix_version.clone());
+            let matches_tool = data
+                .version
+                .as_ref()
+                .zip(expected_version.as_ref())
+                .map(|(version, expected)| version == expected);
+            let (family, warnings) = data
+                .version
+                .as_ref()
+                .zip(installed_tool)
+                .map(|(version, tool)| {
+                    let (family, warnings) =
+                        steam::compat_data::check_prefix(version, tool, &installed_tools);
+                    (Some(family), warnings)
+                })
+                .unwrap_or_default();
+
+            Prefix {
+                app: to_app(&data.app_id, &app_names, &manifests, &registry, &shortcuts),
+                size: dir_size(&data.path),
+                path: data.path,
+                version: data.version,
+                modified: data
+                    .modified
+                    .and_then(|m| m.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs()),
+                tool: tool.map(|t| t.to_string()),
+                reason,
+                display_name: installed_tool.map(|t| t.display_name.to_string()),
+                expected_version,
+                matches_tool,
+                family,
+                warnings,
+            }
+        })
+        .collect();
+    prefixes.sort_by(|a, b| a.app.name.cmp(&b.app.name));
+
+    Ok(Prefixes(prefixes))
+}
+
+/// compatdata and shadercache directories of apps without a manifest that aren't shortcuts
+/// either. App 0 and compatibility tools are never included.
+pub fn find_orphans(steam_home: &Path, action: OrphanAction) -> Result<Orphans> {
+    // Deleting based on a config that couldn't be read could remove an app's prefix
+    let (libraries, shortcuts) = if action == OrphanAction::Delete {
+        let refuse = |e: Box<dyn std::error::Error>| format!("Not deleting anything: {}", e);
+        (
+            steam::library_folders::try_find_libraries(steam_home).map_err(refuse)?,
+            steam::shortcuts::try_parse_shortcuts(steam_home).map_err(refuse)?,
+        )
+    } else {
+        (
+            steam::library_folders::find_libraries(steam_home),
+            steam::shortcuts::parse_shortcuts(steam_home)?,
+        )
+    };
+    let manifest_ids: HashSet<AppId> = libraries
+        .iter()
+        .flat_map(|library| steam::app_manifest::find_app_manifest_ids(library))
+        .collect();
+    let shortcut_ids: HashSet<AppId> = shortcuts
+        .into_values()
+        .flatten()
+        .map(|shortcut| shortcut.app_id)
+        .collect();
+    let steam_play = steam::app_info::read_steam_play_manifest(steam_home).unwrap_or_default();
+    let steam_tools = steam::installed_tools::steam_tool_names(&steam_play);
+
+    let candidates: Vec<(AppId, OrphanKind, PathBuf)> = libraries
+        .iter()
+        .flat_map(|library| {
+            [
+                ("compatdata", OrphanKind::CompatData),
+                ("shadercache", OrphanKind::ShaderCache),
+            ]
+            .into_iter()
+            .flat_map(move |(dir_name, kind)| {
+                steam::compat_data::find_app_dirs(library, dir_name)
+                    .into_iter()
+                    .map(move |(app_id, path)| (app_id, kind, path))
+            })
+        })
+        .filter(|(app_id, _, _)| {
+            *app_id != steam::DEFAULT_PROTON_APP_ID
+                && !manifest_ids.contains(app_id)
+                && !shortcut_ids.contains(app_id)
+                && !steam_tools.contains_key(app_id)
+        })
+        .collect();
+
+    let whitelist = candidates.iter().map(|(app_id, _, _)| app_id).collect();
+    let registry = get_registry(steam_home, &whitelist)?;
+    let (app_names, _) = get_app_names(steam_home, &whitelist, &registry)?;
+
+    let mut dirs = Vec::new();
+    for (app_id, kind, path) in candidates {
+        let size = dir_size(&path);
+        let mut error = None;
+        if action == OrphanAction::Delete {
+            match fs::remove_dir_all(&path) {
+                Ok(()) => log::info!("Deleted {}", path.display()),
+                Err(e) => error = Some(e.to_string()),
+            }
+        }
+
+        dirs.push(OrphanDir {
+            name: app_names.get(&app_id).cloned(),
+            app_id,
+            kind,
+            path,
+            size,
+            deleted: action == OrphanAction::Delete && error.is_none(),
+            error,
+        });
+    }
+    dirs.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(Orphans { dirs, action })
+}
+
+/// Apps Valve pinned to a specific tool in the Steam Play manifest, grouped by tool
+pub fn find_valve_defaults(steam_home: &Path) -> Result<ValveDefaults> {
+    let steam_play = steam::app_info::read_steam_play_manifest(steam_home)
+        .ok_or("Couldn't find the Steam Play manifest in appinfo.vdf")?;
+
+    let whitelist = steam_play.app_mappings.keys().collect();
+    let registry = get_registry(steam_home, &whitelist)?;
+    let (app_names, shortcuts) = get_app_names(steam_home, &whitelist, &registry)?;
+    let manifests = steam::library_folders::find_installed_apps(steam_home);
+    let installed_tools = steam::installed_tools::find_installed_tools(steam_home);
+
+    let mut defaults: BTreeMap<String, PinnedTool> = BTreeMap::new();
+    for (id, mapping) in &steam_play.app_mappings {
+        let pinned = defaults
+            .entry(mapping.tool.to_string())
+            .or_insert_with(|| PinnedTool {
+                tool: installed_tools
+                    .iter()
+                    .find(|t| t.name == mapping.tool)
+                    .cloned(),
+                display_name: steam_play
+                    .compat_tools
+                    .get(&mapping.tool)
+                    .and_then(|tool| tool.display_name.clone()),
+                apps: Vec::new(),
+            });
+        pinned.apps.push(PinnedApp {
+            app: to_app(id, &app_names, &manifests, &registry, &shortcuts),
+            config: mapping.config.clone(),
+            comment: mapping.comment.clone(),
+        });
+    }
+    for pinned in defaults.values_mut() {
+        pinned.apps.sort_by(|a, b| a.app.name.cmp(&b.app.name));
+    }
+
+    Ok(ValveDefaults(defaults))
+}
+
+/// Installed compatibility tools that no app is mapped to and that aren't the default tool.
+/// Steam Linux Runtimes are never mapped to apps, so they're left out.
+pub fn find_unused_tools(steam_home: &Path) -> Result<UnusedTools> {
+    let tool_mapping = get_tool_mapping(steam_home)?;
+    let mut tools: Vec<CompatTool> = steam::installed_tools::find_installed_tools(steam_home)
+        .into_iter()
+        .filter(|tool| !tool.is_runtime() && !tool_mapping.uses_tool(&tool.name))
+        .map(|tool| CompatTool {
+            size: dir_size(&tool.install_path),
+            name: tool.name,
+            install_path: tool.install_path,
+        })
+        .collect();
+    tools.sort_by(|a, b| a.name.cmp(&b.name));
 
-            for app in apps {
-                write!(f, "{:i$}", "", i = INDENT_WIDTH)?;
-                app.fmt(f)?;
-            }
+    Ok(UnusedTools(tools))
+}
 
-            if i < self.0.len() - 1 {
-                writeln!(f)?;
-            }
+/// Maps an app, by id or name, to an installed compatibility tool, by name or display name
+pub fn set_tool(
+    steam_home: &Path,
+    app: &str,
+    tool: &str,
+    backups: &BackupStore,
+) -> Result<ToolChange> {
+    let installed_tools = steam::installed_tools::find_installed_tools(steam_home);
+    let tool = find_tool(&installed_tools, tool)?;
+    let manifests = steam::library_folders::find_installed_apps(steam_home);
+    let app_id = resolve_app_id(steam_home, app, &manifests)?;
+    let whitelist = HashSet::from([&app_id]);
+    let registry = get_registry(steam_home, &whitelist)?;
+    let (app_names, shortcuts) = get_app_names(steam_home, &whitelist, &registry)?;
+    if !app_names.contains_key(&app_id) && !manifests.contains_key(&app_id) {
+        return Err(format!("App {} doesn't exist", app_id).into());
+    }
+    let app = to_app(&app_id, &app_names, &manifests, &registry, &shortcuts);
+    warn_prefix_mismatch(
+        &app,
+        prefix_versions(steam_home).get(&app_id),
+        tool,
+        &installed_tools,
+    );
+
+    let config_path = config_path(steam_home);
+    let mut config = Document::parse(read_text_config(&config_path)?)?;
+    let from = steam::parse_compat_tool_mapping(config.as_str())?
+        .tool_for(&app_id)
+        .map(|t| t.to_string());
+    steam::set_compat_tool(&mut config, &app_id, &tool.name)?;
+    write_text_config(&config_path, config.as_str(), backups)?;
+
+    Ok(ToolChange {
+        app,
+        from,
+        to: tool.name.to_string(),
+    })
+}
+
+/// Maps every app using one tool to another. `from` doesn't need to be installed.
+pub fn migrate_tool(
+    steam_home: &Path,
+    from: &str,
+    to: &str,
+    only_installed: bool,
+    dry_run: bool,
+    backups: &BackupStore,
+) -> Result<Migration> {
+    let installed_tools = steam::installed_tools::find_installed_tools(steam_home);
+    let to_tool = find_tool(&installed_tools, to)?;
+    let to = to_tool.name.to_string();
+    let config_path = config_path(steam_home);
+    let mut config = Document::parse(read_text_config(&config_path)?)?;
+    let tool_mapping = steam::parse_compat_tool_mapping(config.as_str())?;
+    let from = match find_tool(&installed_tools, from) {
+        Ok(tool) if !tool_mapping.contains_tool(from) => tool.name.to_string(),
+        _ => from.to_string(),
+    };
+    if from == to {
+        return Err(format!("Apps using {} already use {}", from, to).into());
+    }
+
+    let app_ids = tool_mapping.apps_for(&from);
+    let whitelist = app_ids.iter().collect();
+    let registry = get_registry(steam_home, &whitelist)?;
+    let (app_names, shortcuts) = get_app_names(steam_home, &whitelist, &registry)?;
+    let manifests = steam::library_folders::find_installed_apps(steam_home);
+    let prefix_versions = prefix_versions(steam_home);
+
+    let mut changes = Vec::new();
+    for id in app_ids {
+        let app = to_app(id, &app_names, &manifests, &registry, &shortcuts);
+        if only_installed && app.install_state != InstallState::Installed {
+            continue;
         }
 
-        Ok(())
+        warn_prefix_mismatch(&app, prefix_versions.get(id), to_tool, &installed_tools);
+        steam::set_compat_tool(&mut config, id, &to)?;
+        changes.push(ToolChange {
+            app,
+            from: Some(from.to_string()),
+            to: to.to_string(),
+        });
     }
-}
+    changes.sort_by(|a, b| a.app.name.cmp(&b.app.name));
 
-pub struct App {
-    pub name: String,
-    pub install_state: InstallState,
+    if !dry_run && !changes.is_empty() {
+        write_text_config(&config_path, config.as_str(), backups)?;
+    }
+
+    Ok(Migration {
+        from,
+        to,
+        changes,
+        dry_run,
+    })
 }
 
-impl Display for App {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        if self.install_state != InstallState::Installed {
-            writeln!(f, "{} ({})", self.name, self.install_state)?;
-        } else {
-            writeln!(f, "{}", self.name)?;
-        }
+/// Whether Steam is running and who's logged in
+pub fn steam_status(steam_home: &Path) -> Result<SteamStatus> {
+    let pid = steam::process::find_steam_pid(steam_home);
+    let active_user = match pid {
+        Some(_) => read_text_config(steam_home.join("registry.vdf"))
+            .and_then(|registry| steam::registry::parse_active_user(&registry))
+            .unwrap_or_else(|e| {
+                log::warn!("Couldn't read the active user from registry.vdf: {}", e);
+                None
+            })
+            .map(SteamId64::from),
+        None => None,
+    };
+    let user = match active_user {
+        Some(id) => Some(id),
+        None => steam::get_most_recent_user(steam_home)?,
+    }
+    .map(|id| -> Result<User> {
+        let name = steam::get_display_name(steam_home, &id)?;
+        Ok(User { id, name })
+    })
+    .transpose()?;
 
-        Ok(())
+    Ok(SteamStatus {
+        running: pid.is_some(),
+        pid,
+        user,
+    })
+}
+
+/// Errors if Steam is running since it overwrites its config files when it exits. When `wait`
+/// is set, blocks until Steam exits instead.
+pub fn ensure_steam_closed(steam_home: &Path, wait: bool) -> Result<()> {
+    let Some(pid) = steam::process::find_steam_pid(steam_home) else {
+        return Ok(());
+    };
+    if !wait {
+        return Err(format!(
+            "Steam is running (pid {}) and would overwrite any changes when it exits. \
+             Exit Steam first, or use --wait.",
+            pid
+        )
+        .into());
     }
+
+    log::warn!("Waiting for Steam (pid {}) to exit", pid);
+    steam::process::wait_for_exit(steam_home, std::time::Duration::from_secs(1));
+
+    Ok(())
 }
 
-pub struct LaunchOptions {
-    pub app: App,
-    pub value: String,
+/// Backups of changed config files, oldest first
+pub fn list_backups(backups: &BackupStore) -> Result<Backups> {
+    Ok(Backups(backups.list()?))
 }
 
-pub struct LaunchOptionsConfig(BTreeMap<String, Vec<LaunchOptions>>);
-impl Display for LaunchOptionsConfig {
-    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        for (user_id, apps) in self.0.iter() {
-            let mut indent = 0;
-            if self.0.len() != 1 {
-                writeln!(f, "{}", user_id)?;
-                indent += 1;
-            }
+/// Copies a backup over the file it was taken from. The current file is backed up first, so a
+/// restore can be undone as well.
+pub fn restore_backup(backups: &BackupStore, id: &str) -> Result<RestoredBackup> {
+    let backup = backups.find(id)?;
+    let contents = fs::read(&backup.path)?;
+    write_config(&backup.source, &contents, backups)?;
 
-            for (i, options) in apps.iter().enumerate() {
-                write!(f, "{:i$}", "", i = indent * INDENT_WIDTH)?;
-                options.app.fmt(f)?;
-                writeln!(
-                    f,
-                    "{:i$}{}",
-                    "",
-                    options.value.replace("\\\"", "\""),
-                    i = (indent + 1) * INDENT_WIDTH
-                )?;
+    Ok(RestoredBackup(backup))
+}
 
-                if i < apps.len() - 1 {
-                    writeln!(f)?;
-                }
-            }
-        }
+/// Removes all but the newest `keep` backups. Returns the removed ones.
+pub fn prune_backups(backups: &BackupStore, keep: usize) -> Result<PrunedBackups> {
+    Ok(PrunedBackups(backups.prune(keep)?))
+}
 
-        Ok(())
-    }
+/// Finds a tool by its internal name or, case insensitively, its display name
+fn find_tool<'a>(installed_tools: &'a [InstalledTool], name: &str) -> Result<&'a InstalledTool> {
+    installed_tools
+        .iter()
+        .find(|t| t.name == name)
+        .or_else(|| {
+            installed_tools
+                .iter()
+                .find(|t| t.display_name.eq_ignore_ascii_case(name))
+        })
+        .ok_or_else(|| format!("'{}' isn't an installed compatibility tool", name).into())
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub enum InstallState {
-    NotInstalled,
-    Installed,
-    Shortcut,
-    Unknown,
+/// Prefix version of every app with a prefix, in any library
+fn prefix_versions(steam_home: &Path) -> HashMap<AppId, String> {
+    steam::library_folders::find_libraries(steam_home)
+        .iter()
+        .flat_map(|library| steam::compat_data::find_compat_data(library))
+        .filter_map(|data| Some((data.app_id, data.version?)))
+        .collect()
 }
 
-impl Display for InstallState {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            InstallState::NotInstalled => write!(f, "Not Installed"),
-            InstallState::Installed => write!(f, "Installed"),
-            InstallState::Shortcut => write!(f, "Shortcut"),
-            InstallState::Unknown => write!(f, "Unknown Install State"),
+/// Warns if running an app's existing prefix with `tool` could break it
+fn warn_prefix_mismatch(
+    app: &App,
+    version: Option<&String>,
+    tool: &InstalledTool,
+    installed_tools: &[InstalledTool],
+) {
+    let Some(version) = version else {
+        return;
+    };
+    let (family, warnings) = steam::compat_data::check_prefix(version, tool, installed_tools);
+    for warning in warnings {
+        match warning {
+            PrefixWarning::ToolOlder => log::warn!(
+                "{} ({}): {} is older than the prefix ({}), which may break it",
+                app.name,
+                app.id,
+                tool.display_name,
+                version
+            ),
+            PrefixWarning::DifferentFamily => log::warn!(
+                "{} ({}): prefix was created by {} ({}), but {} is {}",
+                app.name,
+                app.id,
+                family,
+                version,
+                tool.display_name,
+                tool.family()
+            ),
         }
     }
 }
 
-pub fn parse_launch_options(steam_home: &Path) -> Result<LaunchOptionsConfig> {
-    let launch_options = steam::parse_launch_options_mapping(steam_home)?;
-    let unique_apps = launch_options
+/// Parses an app id or finds the installed app or shortcut with a matching name
+fn resolve_app_id(
+    steam_home: &Path,
+    app: &str,
+    manifests: &HashMap<AppId, AppManifest>,
+) -> Result<AppId> {
+    if let Ok(id) = app.parse() {
+        return Ok(id);
+    }
+
+    let shortcuts = steam::shortcuts::parse_shortcuts(steam_home)?;
+    let mut matches: Vec<AppId> = manifests
         .values()
-        .flat_map(|ids| ids.iter().map(|o| &o.app_id))
+        .map(|m| (m.app_id, &m.name))
+        .chain(shortcuts.values().flatten().map(|s| (s.app_id, &s.name)))
+        .filter(|(_, name)| name.eq_ignore_ascii_case(app))
+        .map(|(id, _)| id)
         .collect();
-    let registry = get_registry(steam_home, &unique_apps)?;
-    let (app_names, shortcuts) = get_app_names(steam_home, &unique_apps, &registry)?;
+    matches.sort();
+    matches.dedup();
 
-    let mut result = BTreeMap::new();
-    for (id, options) in launch_options {
-        let mut o: Vec<LaunchOptions> = options
-            .iter()
-            .map(|l| {
-                let app = to_app(&l.app_id, &app_names, &registry, &shortcuts);
+    match matches[..] {
+        [id] => Ok(id),
+        [] => Err(format!(
+            "No installed app or shortcut is named '{}'. Try its app id instead.",
+            app
+        )
+        .into()),
+        _ => Err(format!("Multiple apps are named '{}'. Use an app id instead.", app).into()),
+    }
+}
 
-                LaunchOptions {
-                    app,
-                    value: l.options.to_string(),
-                }
+/// Finds a user by persona name, case insensitively, then by account id or 64 bit Steam id.
+/// Defaults to the most recent user.
+fn resolve_user(steam_home: &Path, user: Option<&str>) -> Result<User> {
+    let id = match user {
+        None => steam::get_most_recent_user(steam_home)?
+            .ok_or("No user has logged in to Steam. Use --user to choose one.")?,
+        Some(user) => match (
+            steam::find_user_by_name(steam_home, user)?,
+            user.parse::<UserId>(),
+            user.parse::<SteamId64>(),
+        ) {
+            (Some(id), _, _) => id,
+            (_, Ok(account_id), _) => SteamId64::from(account_id),
+            (_, _, Ok(id)) => id,
+            _ => return Err(format!("No user is named '{}'", user).into()),
+        },
+    };
+    let name = steam::get_display_name(steam_home, &id)?;
+
+    Ok(User { id, name })
+}
+
+fn config_path(steam_home: &Path) -> PathBuf {
+    steam::root_dir(steam_home).join("config/config.vdf")
+}
+
+/// Works out which tool an app runs with
+struct ToolResolver {
+    tool_mapping: CompatToolMapping,
+    steam_play: SteamPlayManifest,
+    /// Valve's tools, by app id
+    steam_tools: HashMap<AppId, String>,
+    app_infos: HashMap<AppId, AppInfo>,
+}
+
+impl ToolResolver {
+    /// Reads the tool mapping and Steam Play manifest, plus appinfo for apps where `include`
+    /// returns true
+    fn load(steam_home: &Path, include: impl Fn(&AppId) -> bool) -> Result<Self> {
+        let tool_mapping = get_tool_mapping(steam_home)?;
+        let app_infos: HashMap<AppId, AppInfo> =
+            steam::app_info::read_app_info(&app_info_path(steam_home), |id| {
+                include(id) || id == &STEAM_PLAY_MANIFEST_APP_ID
             })
+            .into_iter()
+            .map(|info| (info.app_id, info))
             .collect();
-        o.sort_by(|a, b| a.app.name.cmp(&b.app.name));
-        let username = steam::get_display_name(steam_home, &id)?;
-        result.insert(username, o);
+        let steam_play = app_infos
+            .get(&STEAM_PLAY_MANIFEST_APP_ID)
+            .map(SteamPlayManifest::from_app_info)
+            .unwrap_or_default();
+        let steam_tools = steam::installed_tools::steam_tool_names(&steam_play);
+
+        Ok(Self {
+            tool_mapping,
+            steam_play,
+            steam_tools,
+            app_infos,
+        })
     }
-    Ok(LaunchOptionsConfig(result))
-}
 
-pub fn parse_tool_mapping(steam_home: &Path) -> Result<CompatToolConfig> {
-    let config_path = steam_home.join("root/config/config.vdf");
-    log::debug!("Parsing {}", config_path.display());
-    let config_lines = open_text_config(config_path)?;
-    let tool_mapping = steam::parse_compat_tool_mapping(config_lines);
-    let mut unique_apps = tool_mapping.apps();
-    unique_apps.remove(&steam::AppId::new(0));
+    /// Names from registry.vdf, falling back to appinfo.vdf
+    fn app_names(&self, registry: &Registry) -> HashMap<AppId, String> {
+        let mut app_names = registry.app_names.clone();
+        for info in self.app_infos.values() {
+            if let Some(name) = info.name() {
+                app_names
+                    .entry(info.app_id)
+                    .or_insert_with(|| name.to_string());
+            }
+        }
 
-    let registry = get_registry(steam_home, &unique_apps)?;
-    let (app_names, shortcuts) = get_app_names(steam_home, &unique_apps, &registry)?;
+        app_names
+    }
 
-    let config = tool_mapping
-        .into_iter()
-        .map(|(tool, ids)| {
-            let mut names: Vec<App> = ids
-                .iter()
-                .map(|id| to_app(id, &app_names, &registry, &shortcuts))
-                .collect();
-            names.sort_by(|a, b| a.name.cmp(&b.name));
-            (tool, names)
-        })
-        .collect();
+    /// Whether the app is a compatibility tool, runtime or other non-game app
+    fn is_tool(&self, app_id: &AppId) -> bool {
+        self.steam_tools.contains_key(app_id)
+            || self
+                .app_infos
+                .get(app_id)
+                .and_then(AppInfo::app_type)
+                .is_some_and(|t| t.eq_ignore_ascii_case("tool") || t.eq_ignore_ascii_case("config"))
+    }
 
-    Ok(CompatToolConfig(config))
+    /// A tool selected in the app's properties wins over Valve's choice, which wins over the
+    /// default tool for Windows-only apps. Shortcuts only use a tool selected in their properties.
+    fn resolve(&self, app_id: &AppId) -> (Option<&str>, ToolReason) {
+        if let Some(tool) = self.tool_mapping.tool_for(app_id) {
+            (Some(tool), ToolReason::UserOverride)
+        } else if let Some(mapping) = self.steam_play.app_mappings.get(app_id) {
+            (Some(mapping.tool.as_str()), ToolReason::ValveDefault)
+        } else if steam::shortcuts::is_shortcut_id(app_id) {
+            (None, ToolReason::NoTool)
+        } else if self
+            .app_infos
+            .get(app_id)
+            .is_some_and(AppInfo::supports_linux)
+        {
+            (None, ToolReason::Native)
+        } else if let Some(tool) = self.tool_mapping.default_tool.as_deref() {
+            (Some(tool), ToolReason::GlobalDefault)
+        } else {
+            (None, ToolReason::NoTool)
+        }
+    }
+}
+
+fn get_tool_mapping(steam_home: &Path) -> Result<CompatToolMapping> {
+    let config_path = config_path(steam_home);
+    log::debug!("Parsing {}", config_path.display());
+    let config = read_text_config(config_path)?;
+
+    steam::parse_compat_tool_mapping(&config)
 }
 
+/// App manifests are preferred over registry.vdf since its installed flag may be stale
 fn install_state(
     app_id: &AppId,
+    manifests: &HashMap<AppId, AppManifest>,
     registry: &Registry,
-    shortcuts: &HashMap<AppId, String>,
+    shortcuts: &HashMap<AppId, Shortcut>,
 ) -> InstallState {
-    if registry.app_is_installed(app_id) {
+    if let Some(manifest) = manifests.get(app_id) {
+        if manifest.state_flags.is_installed() {
+            InstallState::Installed
+        } else {
+            InstallState::NotInstalled
+        }
+    } else if registry.app_is_installed(app_id) {
         InstallState::Installed
     } else if shortcuts.contains_key(app_id) {
         InstallState::Shortcut
@@ -175,22 +2657,22 @@ fn install_state(
 fn get_registry(steam_home: &Path, whitelist: &HashSet<&AppId>) -> Result<Registry> {
     let registry_path = steam_home.join("registry.vdf");
     log::debug!("Parsing {}", registry_path.display());
-    let registry_lines = open_text_config(registry_path)?;
+    let registry = read_text_config(registry_path)?;
 
-    Ok(steam::registry::parse_registry(registry_lines, whitelist))
+    steam::registry::parse_registry(&registry, whitelist)
 }
 
 fn get_app_names(
     steam_home: &Path,
     whitelist: &HashSet<&AppId>,
     registry: &Registry,
-) -> Result<(HashMap<AppId, String>, HashMap<AppId, String>)> {
+) -> Result<(HashMap<AppId, String>, HashMap<AppId, Shortcut>)> {
     let mut shortcuts = HashMap::new();
     let mut app_names = registry.app_names.clone();
     log::debug!("Found {} name(s) from registry.vdf", app_names.len());
 
     if app_names.len() != whitelist.len() {
-        let appinfo_path = steam_home.join("root/appcache/appinfo.vdf");
+        let appinfo_path = app_info_path(steam_home);
         log::debug!("Parsing {}", appinfo_path.display());
         let missing_names = whitelist
             .difference(&HashSet::from_iter(app_names.keys()))
@@ -209,7 +2691,7 @@ fn get_app_names(
             .collect::<Vec<&AppId>>();
         shortcuts = steam::shortcuts::parse_names(steam_home, &missing_names)?;
         log::debug!("Found {} name(s) from shortcuts.vdf", shortcuts.len());
-        app_names.extend(shortcuts.clone());
+        app_names.extend(shortcuts.values().map(|s| (s.app_id, s.name.clone())));
     }
 
     Ok((app_names, shortcuts))
@@ -218,31 +2700,650 @@ fn get_app_names(
 fn to_app(
     id: &AppId,
     app_names: &HashMap<AppId, String>,
+    manifests: &HashMap<AppId, AppManifest>,
     registry: &Registry,
-    shortcuts: &HashMap<AppId, String>,
+    shortcuts: &HashMap<AppId, Shortcut>,
 ) -> App {
-    let name = match app_names.get(id) {
+    let name = match app_names
+        .get(id)
+        .or_else(|| manifests.get(id).map(|m| &m.name))
+    {
         Some(n) => n.to_string(),
         None => {
             log::info!("{} is possibly a deleted shortcut", id);
             format!("Unknown (Id: {})", id)
         }
     };
-    let install_state = install_state(id, registry, shortcuts);
+    let install_state = install_state(id, manifests, registry, shortcuts);
+    let installation = manifests.get(id).map(|manifest| Installation {
+        library_path: manifest.library_path.clone(),
+        size_on_disk: manifest.size_on_disk,
+        state_flags: manifest.state_flags,
+    });
     App {
+        id: *id,
         name,
         install_state,
+        installation,
     }
 }
 
-fn open_text_config<P>(path: P) -> Result<impl Iterator<Item = String>>
+/// Errors if the file isn't valid UTF-8, since replacing invalid bytes would change them when
+/// the file is written back
+fn read_text_config<P>(path: P) -> Result<String>
 where
     P: AsRef<Path>,
 {
-    let file = fs::File::open(path)?;
-    let lines = std::io::BufReader::new(file)
-        .lines()
-        .map_while(std::result::Result::ok);
+    let path = path.as_ref();
+    let bytes = fs::read(path)?;
+
+    String::from_utf8(bytes)
+        .map_err(|e| format!("'{}' isn't valid UTF-8: {}", path.display(), e).into())
+}
+
+fn write_text_config(path: &Path, contents: &str, backups: &BackupStore) -> Result<()> {
+    write_config(path, contents.as_bytes(), backups)
+}
+
+/// Backs up the current file to `backups`, then writes to a temporary file first so a failed
+/// write can't leave a truncated config behind
+fn write_config(path: &Path, contents: &[u8], backups: &BackupStore) -> Result<()> {
+    if path.exists() {
+        backups.create(path)?;
+    }
+
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    fs::write(&tmp_path, contents)?;
+    if let Ok(metadata) = fs::metadata(path) {
+        fs::set_permissions(&tmp_path, metadata.permissions())?;
+    }
+    fs::rename(&tmp_path, path)
+        .map_err(|e| format!("Couldn't write '{}': {}", path.display(), e))?;
+
+    Ok(())
+}
+
+/// Total size of all files in a directory. Doesn't follow symlinks.
+fn dir_size(path: &Path) -> u64 {
+    fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| Some((entry.path(), entry.metadata().ok()?)))
+        .map(|(path, metadata)| {
+            if metadata.is_dir() {
+                dir_size(&path)
+            } else {
+                metadata.len()
+            }
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A Steam home with GE-Proton7-55 and GE-Proton8-25 installed. Portal 2 (620) has a
+    /// manifest, Team Fortress 2 (440) is installed according to registry.vdf and
+    /// Counter-Strike 2 (730) isn't installed. `mapping` is written to config.vdf.
+    fn steam_home(mapping: &[(u64, &str)]) -> TempDir {
+        let home = tempfile::tempdir().unwrap();
+        let root = home.path().join("root");
+        for tool in ["GE-Proton7-55", "GE-Proton8-25"] {
+            let dir = root.join("compatibilitytools.d").join(tool);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(
+                dir.join("compatibilitytool.vdf"),
+                format!(
+                    r#""compatibilitytools" {{ "compat_tools" {{ "{0}" {{ "install_path" "." "display_name" "{0}" }} }} }}"#,
+                    tool
+                ),
+            )
+            .unwrap();
+        }
+        fs::create_dir_all(root.join("userdata")).unwrap();
+        fs::create_dir_all(root.join("steamapps")).unwrap();
+        fs::write(
+            root.join("steamapps/appmanifest_620.acf"),
+            r#""AppState" { "appid" "620" "name" "Portal 2" "installdir" "Portal 2" "StateFlags" "4" }"#,
+        )
+        .unwrap();
+        fs::write(
+            home.path().join("registry.vdf"),
+            r#""Registry" { "HKCU" { "Software" { "Valve" { "Steam" { "apps" {
+                "620" { "installed" "1" "name" "Portal 2" }
+                "440" { "installed" "1" "name" "Team Fortress 2" }
+                "730" { "installed" "0" "name" "Counter-Strike 2" }
+            } } } } } }"#,
+        )
+        .unwrap();
+
+        let apps: String = mapping
+            .iter()
+            .map(|(id, tool)| {
+                format!(
+                    "\t\t\t\t\t\"{}\"\n\t\t\t\t\t{{\n\t\t\t\t\t\t\"name\"\t\t\"{}\"\n\t\t\t\t\t\t\"config\"\t\t\"\"\n\t\t\t\t\t\t\"priority\"\t\t\"250\"\n\t\t\t\t\t}}\n",
+                    id, tool
+                )
+            })
+            .collect();
+        fs::create_dir_all(root.join("config")).unwrap();
+        fs::write(
+            root.join("config/config.vdf"),
+            format!(
+                "\"InstallConfigStore\"\n{{\n\t\"Software\"\n\t{{\n\t\t\"Valve\"\n\t\t{{\n\t\t\t\"Steam\"\n\t\t\t{{\n\t\t\t\t\"CompatToolMapping\"\n\t\t\t\t{{\n{}\t\t\t\t}}\n\t\t\t}}\n\t\t}}\n\t}}\n}}\n",
+                apps
+            ),
+        )
+        .unwrap();
+
+        home
+    }
+
+    fn backups(home: &TempDir) -> BackupStore {
+        BackupStore::new(home.path().join("backups"))
+    }
+
+    /// Writes the launch options of user 12345678 (player1) to their localconfig.vdf
+    fn local_config(home: &TempDir, apps: &[(u64, &str)]) {
+        fs::write(
+            home.path().join("root/config/loginusers.vdf"),
+            r#""users" { "76561197972611406" { "AccountName" "player1" "PersonaName" "Player 1" } }"#,
+        )
+        .unwrap();
+        let apps: String = apps
+            .iter()
+            .map(|(id, options)| format!(r#""{}" {{ "LaunchOptions" "{}" }}"#, id, options))
+            .collect();
+        let config = home.path().join("root/userdata/12345678/config");
+        fs::create_dir_all(&config).unwrap();
+        fs::write(
+            config.join("localconfig.vdf"),
+            format!(
+                r#""UserLocalConfigStore" {{ "Software" {{ "Valve" {{ "Steam" {{ "apps" {{ {} }} }} }} }} }}"#,
+                apps
+            ),
+        )
+        .unwrap();
+    }
+
+    fn tool_mapping(home: &TempDir) -> CompatToolMapping {
+        get_tool_mapping(home.path()).unwrap()
+    }
+
+    #[test]
+    fn set_tool_writes_config_and_backs_it_up() {
+        let home = steam_home(&[(620, "GE-Proton7-55")]);
+
+        let change = set_tool(home.path(), "Portal 2", "GE-Proton8-25", &backups(&home)).unwrap();
+
+        assert_eq!(change.from.as_deref(), Some("GE-Proton7-55"));
+        assert_eq!(change.to, "GE-Proton8-25");
+        assert_eq!(
+            tool_mapping(&home).tool_for(&AppId::new(620)),
+            Some("GE-Proton8-25")
+        );
+        assert_eq!(list_backups(&backups(&home)).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn set_tool_leaves_invalid_utf8_config_alone() {
+        let home = steam_home(&[(620, "GE-Proton7-55")]);
+        let config_path = config_path(home.path());
+        let mut contents = fs::read(&config_path).unwrap();
+        contents.splice(0..0, b"// \xff\n".iter().copied());
+        fs::write(&config_path, &contents).unwrap();
+
+        assert!(set_tool(home.path(), "620", "GE-Proton8-25", &backups(&home)).is_err());
+        assert_eq!(fs::read(&config_path).unwrap(), contents);
+    }
+
+    #[test]
+    fn set_tool_rejects_tools_that_arent_installed() {
+        let home = steam_home(&[(620, "GE-Proton7-55")]);
+
+        assert!(set_tool(home.path(), "620", "proton_9", &backups(&home)).is_err());
+        assert_eq!(
+            tool_mapping(&home).tool_for(&AppId::new(620)),
+            Some("GE-Proton7-55")
+        );
+    }
+
+    #[test]
+    fn migrate_only_installed_skips_other_apps() {
+        let home = steam_home(&[
+            (620, "GE-Proton7-55"),
+            (440, "GE-Proton7-55"),
+            (730, "GE-Proton7-55"),
+        ]);
+
+        let migration = migrate_tool(
+            home.path(),
+            "GE-Proton7-55",
+            "GE-Proton8-25",
+            true,
+            false,
+            &backups(&home),
+        )
+        .unwrap();
+
+        let migrated: Vec<&str> = migration
+            .changes
+            .iter()
+            .map(|c| c.app.name.as_str())
+            .collect();
+        assert_eq!(migrated, ["Portal 2", "Team Fortress 2"]);
+        let mapping = tool_mapping(&home);
+        assert_eq!(mapping.apps_for("GE-Proton8-25").len(), 2);
+        assert_eq!(mapping.tool_for(&AppId::new(730)), Some("GE-Proton7-55"));
+    }
+
+    #[test]
+    fn migrate_dry_run_writes_nothing() {
+        let home = steam_home(&[(620, "GE-Proton7-55")]);
+        let config_path = config_path(home.path());
+        let before = fs::read_to_string(&config_path).unwrap();
+
+        let migration = migrate_tool(
+            home.path(),
+            "GE-Proton7-55",
+            "GE-Proton8-25",
+            false,
+            true,
+            &backups(&home),
+        )
+        .unwrap();
+
+        assert_eq!(migration.changes.len(), 1);
+        assert_eq!(fs::read_to_string(&config_path).unwrap(), before);
+        assert!(list_backups(&backups(&home)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn all_installs_headers_match_rows() {
+        let home = steam_home(&[(620, "GE-Proton7-55")]);
+        local_config(&home, &[(620, "%command% -novid")]);
+        let installs = || {
+            vec![SteamInstall {
+                name: "native".to_string(),
+                home: home.path().to_path_buf(),
+                root: home.path().join("root"),
+            }]
+        };
+        let filter = LaunchOptionsFilter::default();
+
+        let tools = for_all_installs(installs(), parse_tool_mapping).unwrap();
+        let options =
+            for_all_installs(installs(), |h| parse_launch_options(h, false, &filter)).unwrap();
+        let parsed =
+            for_all_installs(installs(), |h| parse_launch_options(h, true, &filter)).unwrap();
+
+        for (headers, rows) in [
+            (tools.headers(), tools.rows()),
+            (options.headers(), options.rows()),
+            (parsed.headers(), parsed.rows()),
+        ] {
+            assert_eq!(rows.len(), 1);
+            assert_eq!(headers.len(), rows[0].len(), "{:?}", headers);
+        }
+    }
+
+    #[test]
+    fn all_installs_reads_native_and_flatpak_separately() {
+        let user_home = tempfile::tempdir().unwrap();
+        let user_home = user_home.path().canonicalize().unwrap();
+        let native_root = user_home.join(".local/share/Steam");
+        let flatpak_dir = user_home.join(".var/app/com.valvesoftware.Steam");
+        let fixtures = [
+            (
+                steam_home(&[(620, "GE-Proton7-55")]),
+                user_home.join(".steam"),
+                &native_root,
+            ),
+            (
+                steam_home(&[(620, "GE-Proton8-25")]),
+                flatpak_dir.join(".steam"),
+                &flatpak_dir.join(".local/share/Steam"),
+            ),
+        ];
+        for (fixture, home, root) in &fixtures {
+            fs::create_dir_all(home).unwrap();
+            fs::create_dir_all(root.parent().unwrap()).unwrap();
+            fs::rename(fixture.path().join("root"), root).unwrap();
+            fs::rename(
+                fixture.path().join("registry.vdf"),
+                home.join("registry.vdf"),
+            )
+            .unwrap();
+            // Flatpak Steam's links are absolute paths inside its sandbox
+            for link in ["root", "steam"] {
+                std::os::unix::fs::symlink(&native_root, home.join(link)).unwrap();
+            }
+        }
+        let installs =
+            steam::locator::find_installs_in(&user_home, &user_home.join(".local/share"), false);
+
+        let all = for_all_installs(installs, parse_tool_mapping).unwrap();
+
+        let tools: Vec<(&str, Vec<&str>)> = all
+            .installs
+            .iter()
+            .map(|r| {
+                (
+                    r.install.name.as_str(),
+                    r.report.tools.keys().map(String::as_str).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            tools,
+            [
+                ("native", vec!["GE-Proton7-55"]),
+                ("flatpak", vec!["GE-Proton8-25"])
+            ]
+        );
+        assert_eq!(
+            all.duplicate_tools.keys().collect::<Vec<_>>(),
+            ["GE-Proton7-55", "GE-Proton8-25"]
+        );
+    }
+
+    #[test]
+    fn lint_report_ends_with_a_newline() {
+        let home = steam_home(&[]);
+        local_config(&home, &[(620, "DXVK_ASYNC=1 DXVK_ASYNC=0 %command%")]);
+
+        let report = lint_launch_options(home.path()).unwrap();
+
+        assert!(report.to_string().ends_with("warning(s)\n"));
+        assert_eq!(LintReport(Vec::new()).to_string(), "No problems found\n");
+    }
+
+    #[test]
+    fn resolves_users_by_name_before_id() {
+        let home = steam_home(&[]);
+        fs::write(
+            home.path().join("root/config/loginusers.vdf"),
+            r#""users" {
+                "76561197972611406" { "AccountName" "player1" "PersonaName" "Player 1" }
+                "76561197960266728" { "AccountName" "player2" "PersonaName" "12345678" }
+            }"#,
+        )
+        .unwrap();
+        let id = |user| {
+            resolve_user(home.path(), Some(user))
+                .unwrap()
+                .id
+                .to_string()
+        };
+
+        assert_eq!(id("12345678"), "76561197960266728");
+        assert_eq!(id("1000"), "76561197960266728");
+        assert_eq!(id("76561197972611406"), "76561197972611406");
+        assert_eq!(id("player 1"), "76561197972611406");
+        assert!(resolve_user(home.path(), Some("Player 3")).is_err());
+    }
+
+    #[test]
+    fn resolves_a_shortcut_shared_by_several_users() {
+        let home = steam_home(&[]);
+        let shortcut = bin_vdf(&["shortcuts", "0", "AppName"], "My Game");
+        for user in ["12345678", "23456789"] {
+            let config = home.path().join("root/userdata").join(user).join("config");
+            fs::create_dir_all(&config).unwrap();
+            fs::write(config.join("shortcuts.vdf"), &shortcut).unwrap();
+        }
+        let manifests = steam::library_folders::find_installed_apps(home.path());
+
+        let id = resolve_app_id(home.path(), "my game", &manifests).unwrap();
+
+        assert!(steam::shortcuts::is_shortcut_id(&id));
+    }
+
+    #[test]
+    fn parses_env_var_filters() {
+        let filter = |s: &str| s.parse::<EnvVarFilter>();
+
+        assert_eq!(
+            filter("DXVK_ASYNC"),
+            Ok(EnvVarFilter {
+                name: "DXVK_ASYNC".to_string(),
+                value: None
+            })
+        );
+        assert_eq!(filter("DXVK_ASYNC=1").unwrap().value.as_deref(), Some("1"));
+        assert_eq!(filter("DXVK_ASYNC=").unwrap().value.as_deref(), Some(""));
+        assert!(filter("=1").is_err());
+    }
+
+    #[test]
+    fn filters_by_env_name_or_value() {
+        let parsed = launch_options::parse_launch_options("DXVK_ASYNC=1 %command%").unwrap();
+        let filter = |env: &str| LaunchOptionsFilter {
+            env: Some(env.parse().unwrap()),
+            wrapper: None,
+        };
+
+        assert!(filter("DXVK_ASYNC").matches(&parsed));
+        assert!(filter("DXVK_ASYNC=1").matches(&parsed));
+        assert!(!filter("DXVK_ASYNC=0").matches(&parsed));
+        assert!(!filter("DXVK_HUD").matches(&parsed));
+    }
+
+    #[test]
+    fn filters_wrappers_by_file_name() {
+        let parsed =
+            launch_options::parse_launch_options("/usr/bin/gamemoderun %command%").unwrap();
+        let filter = |wrapper: &str| LaunchOptionsFilter {
+            env: None,
+            wrapper: Some(wrapper.to_string()),
+        };
+
+        assert!(filter("gamemoderun").matches(&parsed));
+        assert!(!filter("bin").matches(&parsed));
+        assert!(!filter("mangohud").matches(&parsed));
+        assert!(LaunchOptionsFilter::default().matches(&parsed));
+    }
+
+    #[test]
+    fn stats_count_each_app_once() {
+        let home = steam_home(&[]);
+        local_config(
+            &home,
+            &[
+                (
+                    620,
+                    "DXVK_ASYNC=1 DXVK_ASYNC=1 gamemoderun gamemoderun %command%",
+                ),
+                (440, "DXVK_ASYNC=0 %command%"),
+            ],
+        );
+
+        let stats = find_launch_options_stats(home.path()).unwrap();
+
+        let usages: Vec<(OptionKind, &str, usize)> = stats
+            .0
+            .iter()
+            .map(|usage| (usage.kind, usage.name.as_str(), usage.apps))
+            .collect();
+        assert_eq!(
+            usages,
+            [
+                (OptionKind::Env, "DXVK_ASYNC", 2),
+                (OptionKind::Wrapper, "gamemoderun", 1)
+            ]
+        );
+    }
 
-    Ok(lines)
+    #[test]
+    fn replace_env_rejects_invalid_names() {
+        assert!(ReplaceAction::env("DXVK_ASYNC", "1DXVK").is_err());
+        assert!(ReplaceAction::env("DXVK_ASYNC", "DXVK GPLASYNC").is_err());
+        assert!(ReplaceAction::env("DXVK_ASYNC", "DXVK_GPLASYNC").is_ok());
+    }
+
+    #[test]
+    fn replace_env_rejects_renaming_to_itself() {
+        assert!(ReplaceAction::env("DXVK_ASYNC", "DXVK_ASYNC").is_err());
+    }
+
+    #[test]
+    fn replace_renames_env_of_every_app() {
+        let home = steam_home(&[]);
+        local_config(
+            &home,
+            &[(620, "DXVK_ASYNC=1 %command%"), (440, "%command% -novid")],
+        );
+        let action = ReplaceAction::env("DXVK_ASYNC", "DXVK_GPLASYNC").unwrap();
+
+        let replacement =
+            replace_launch_options(home.path(), None, true, &action, false, &backups(&home))
+                .unwrap();
+
+        let rows = replacement.rows();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].len(), replacement.headers().len());
+        assert_eq!(rows[0][4], "DXVK_GPLASYNC=1 %command%");
+        let config = fs::read_to_string(
+            home.path()
+                .join("root/userdata/12345678/config/localconfig.vdf"),
+        )
+        .unwrap();
+        assert!(config.contains("DXVK_GPLASYNC=1 %command%"));
+        assert!(config.contains("%command% -novid"));
+    }
+
+    /// Binary key values for `path`, nested objects with a single string at the end
+    fn bin_vdf(path: &[&str], value: &str) -> Vec<u8> {
+        let (key, objects) = path.split_last().unwrap();
+        let mut contents = Vec::new();
+        for object in objects {
+            contents.extend([&[0x00], object.as_bytes(), b"\0"].concat());
+        }
+        contents.extend([&[0x01], key.as_bytes(), b"\0", value.as_bytes(), b"\0"].concat());
+        contents.extend(vec![0x08; objects.len() + 1]);
+        contents
+    }
+
+    #[test]
+    fn orphans_skip_installed_apps_shortcuts_and_tools() {
+        let home = steam_home(&[]);
+        let root = home.path().join("root");
+        let mut app_info = 0x07564427u32.to_le_bytes().to_vec();
+        app_info.extend(1u32.to_le_bytes());
+        let kv = bin_vdf(
+            &["appinfo", "extended", "compat_tools", "proton_10", "appid"],
+            "3658110",
+        );
+        app_info.extend(891390u32.to_le_bytes());
+        app_info.extend(((kv.len() + 40) as u32).to_le_bytes());
+        app_info.extend([0; 40]);
+        app_info.extend(kv);
+        app_info.extend(0u32.to_le_bytes());
+        fs::create_dir_all(root.join("appcache")).unwrap();
+        fs::write(app_info_path(home.path()), app_info).unwrap();
+        let config = root.join("userdata/12345678/config");
+        fs::create_dir_all(&config).unwrap();
+        fs::write(
+            config.join("shortcuts.vdf"),
+            bin_vdf(&["shortcuts", "0", "appid"], "2583605614"),
+        )
+        .unwrap();
+        // app 0, a manifest, a Steam runtime, a tool from the Steam Play manifest, a shortcut
+        // and an uninstalled app
+        for id in ["0", "620", "1628350", "3658110", "2583605614", "1172620"] {
+            fs::create_dir_all(root.join("steamapps/compatdata").join(id)).unwrap();
+        }
+        fs::create_dir_all(root.join("steamapps/shadercache/1172620")).unwrap();
+
+        let orphans = find_orphans(home.path(), OrphanAction::Delete).unwrap();
+
+        let found: Vec<(AppId, OrphanKind)> = orphans
+            .dirs
+            .iter()
+            .map(|dir| (dir.app_id, dir.kind))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (AppId::new(1172620), OrphanKind::CompatData),
+                (AppId::new(1172620), OrphanKind::ShaderCache)
+            ]
+        );
+        assert!(orphans
+            .dirs
+            .iter()
+            .all(|dir| dir.deleted && !dir.path.exists()));
+        assert_eq!(orphans.failed(), 0);
+        assert!(root.join("steamapps/compatdata/620").exists());
+        assert!(root.join("steamapps/compatdata/2583605614").exists());
+    }
+
+    #[test]
+    fn orphans_keep_apps_whose_manifest_doesnt_parse() {
+        let home = steam_home(&[]);
+        let root = home.path().join("root");
+        fs::write(
+            root.join("steamapps/appmanifest_1172620.acf"),
+            "\"AppState\" {",
+        )
+        .unwrap();
+        fs::create_dir_all(root.join("steamapps/compatdata/1172620")).unwrap();
+
+        let orphans = find_orphans(home.path(), OrphanAction::Delete).unwrap();
+
+        assert!(orphans.dirs.is_empty());
+        assert!(root.join("steamapps/compatdata/1172620").exists());
+    }
+
+    #[test]
+    fn orphans_refuse_to_delete_if_shortcuts_dont_parse() {
+        let home = steam_home(&[]);
+        let root = home.path().join("root");
+        let config = root.join("userdata/12345678/config");
+        fs::create_dir_all(&config).unwrap();
+        fs::write(config.join("shortcuts.vdf"), b"\x00shortcuts\x00\x00").unwrap();
+        fs::create_dir_all(root.join("steamapps/compatdata/2583605614")).unwrap();
+
+        assert!(find_orphans(home.path(), OrphanAction::Delete).is_err());
+        assert!(root.join("steamapps/compatdata/2583605614").exists());
+
+        let orphans = find_orphans(home.path(), OrphanAction::List).unwrap();
+        assert_eq!(orphans.dirs.len(), 1);
+    }
+
+    #[test]
+    fn orphans_refuse_to_delete_if_library_folders_dont_parse() {
+        let home = steam_home(&[]);
+        let root = home.path().join("root");
+        fs::write(
+            root.join("steamapps/libraryfolders.vdf"),
+            "\"libraryfolders\" {",
+        )
+        .unwrap();
+        fs::create_dir_all(root.join("steamapps/compatdata/1172620")).unwrap();
+
+        assert!(find_orphans(home.path(), OrphanAction::Delete).is_err());
+        assert!(root.join("steamapps/compatdata/1172620").exists());
+    }
+
+    #[test]
+    fn migrate_rejects_same_tool() {
+        let home = steam_home(&[(620, "GE-Proton8-25")]);
+
+        assert!(migrate_tool(
+            home.path(),
+            "GE-Proton8-25",
+            "GE-Proton8-25",
+            false,
+            false,
+            &backups(&home)
+        )
+        .is_err());
+    }
 }
@@ -1,18 +1,46 @@
-use clap::{ArgAction, Parser, Subcommand};
-use proton_usage::{parse_launch_options, parse_tool_mapping};
-use std::path::PathBuf;
+use clap::{ArgAction, ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use proton_usage::backup::BackupStore;
+use proton_usage::delimited::{self, Rows};
+use proton_usage::steam::launch_options::EnvVar;
+use proton_usage::steam::locator::{self, SteamInstall};
+use proton_usage::{
+    edit_launch_options, ensure_steam_closed, find_effective_tools, find_launch_options_stats,
+    find_orphans, find_prefixes, find_unused_tools, find_valve_defaults, for_all_installs,
+    lint_launch_options, list_backups, migrate_tool, parse_launch_options, parse_tool_mapping,
+    prune_backups, replace_launch_options, restore_backup, set_tool, steam_status, EnvVarFilter,
+    LaunchOptionsEdit, LaunchOptionsFilter, OrphanAction, ReplaceAction,
+};
+use serde::Serialize;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(version, about)]
 struct Opts {
-    /// Path to the Steam home directory. Default: ~/.steam
+    /// Path to the Steam home directory. Default: the first Steam installation found
     #[clap(short, long)]
     steam_path: Option<PathBuf>,
 
+    /// Steam installation to use when there's more than one (native, flatpak or snap)
+    #[clap(long, conflicts_with = "steam_path")]
+    install: Option<String>,
+
+    /// Run against every Steam installation found. Only for proton and launch-options
+    #[clap(long, conflicts_with_all = ["steam_path", "install"])]
+    all_installs: bool,
+
     /// Output verbosity (-v, -vv, -vvv, etc)
     #[clap(short, long, action = ArgAction::Count)]
     verbose: u8,
 
+    /// Output format
+    #[clap(short, long, value_enum, default_value_t = Format::Text)]
+    format: Format,
+
+    /// Wait for Steam to exit before changing any files, instead of exiting with an error
+    #[clap(long)]
+    wait: bool,
+
     #[command(subcommand)]
     command: Option<Command>,
 }
@@ -23,7 +51,266 @@ enum Command {
     Proton,
 
     /// Lists apps with overridden launch options
-    LaunchOptions,
+    #[command(args_conflicts_with_subcommands = true)]
+    LaunchOptions {
+        /// Split the options into environment variables, wrappers and arguments
+        #[clap(long)]
+        parsed: bool,
+        /// Only apps that set this environment variable, optionally to this value
+        #[clap(long, value_name = "NAME[=VALUE]")]
+        env: Option<EnvVarFilter>,
+        /// Only apps that use this wrapper (e.g. gamemoderun)
+        #[clap(long, value_name = "NAME")]
+        wrapper: Option<String>,
+        #[command(subcommand)]
+        command: Option<LaunchOptionsCommand>,
+    },
+
+    /// Checks launch options for common mistakes. Exits with an error if any are found.
+    Lint,
+
+    /// Lists installed compatibility tools that no app uses
+    Unused,
+
+    /// Lists the compatibility tool each installed game runs with and why
+    Effective,
+
+    /// Lists games Valve runs with a specific compatibility tool
+    ValveDefaults,
+
+    /// Lists Proton prefixes, their version and size
+    Prefixes,
+
+    /// Lists compatdata and shadercache directories of apps that are no longer installed
+    Orphans {
+        /// Delete the directories
+        #[clap(long)]
+        delete: bool,
+        /// With --delete, show what would be deleted without deleting anything
+        #[clap(long, requires = "delete")]
+        dry_run: bool,
+    },
+
+    /// Sets the compatibility tool used by an app
+    Set {
+        /// App id, or the name of an installed app or shortcut
+        app: String,
+        /// Tool name (e.g. proton_8) or display name (e.g. "Proton 8.0")
+        tool: String,
+    },
+
+    /// Moves every app using one compatibility tool to another
+    Migrate {
+        /// Tool currently used by the apps. Doesn't need to be installed.
+        from: String,
+        /// Installed tool to use instead
+        to: String,
+        /// Only migrate apps that are installed
+        #[clap(long)]
+        only_installed: bool,
+        /// Show which apps would be migrated without changing anything
+        #[clap(long)]
+        dry_run: bool,
+    },
+
+    /// Shows whether Steam is running and which user is logged in
+    Status,
+
+    /// Manages backups of changed Steam config files
+    #[command(subcommand)]
+    Backup(BackupCommand),
+}
+
+impl Command {
+    /// Whether the command changes Steam's config or files, which allows repairing the links
+    /// of the Steam home
+    fn writes(&self) -> bool {
+        match self {
+            Command::LaunchOptions {
+                command: Some(command),
+                ..
+            } => !matches!(
+                command,
+                LaunchOptionsCommand::Stats | LaunchOptionsCommand::Replace { dry_run: true, .. }
+            ),
+            Command::Orphans { delete, dry_run } => *delete && !dry_run,
+            Command::Migrate { dry_run, .. } => !dry_run,
+            Command::Set { .. } | Command::Backup(BackupCommand::Restore { .. }) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum LaunchOptionsCommand {
+    /// Shows how many apps set each environment variable and use each wrapper
+    Stats,
+
+    /// Replaces an app's launch options
+    Set {
+        #[command(flatten)]
+        target: EditTarget,
+        /// New launch options (e.g. "gamemoderun %command%")
+        #[clap(allow_hyphen_values = true)]
+        options: String,
+    },
+
+    /// Sets an environment variable in an app's launch options
+    AddEnv {
+        #[command(flatten)]
+        target: EditTarget,
+        #[clap(value_name = "NAME=VALUE")]
+        var: EnvVar,
+    },
+
+    /// Removes an environment variable from an app's launch options
+    RemoveEnv {
+        #[command(flatten)]
+        target: EditTarget,
+        name: String,
+    },
+
+    /// Removes all of an app's launch options
+    Clear {
+        #[command(flatten)]
+        target: EditTarget,
+    },
+
+    /// Renames an environment variable or replaces a pattern in the launch options of every app
+    #[command(group(ArgGroup::new("action").required(true).args(["env", "regex"])))]
+    Replace {
+        /// Renames environment variable OLD to NEW, keeping its value
+        #[clap(long, num_args = 2, value_names = ["OLD", "NEW"])]
+        env: Option<Vec<String>>,
+        /// Replaces every match of PATTERN. $1 or ${name} in REPLACEMENT insert capture groups.
+        #[clap(long, num_args = 2, value_names = ["PATTERN", "REPLACEMENT"])]
+        regex: Option<Vec<String>>,
+        /// User id or persona name. Default: the user who logged in most recently
+        #[clap(long)]
+        user: Option<String>,
+        /// Change the apps of every user
+        #[clap(long, conflicts_with = "user")]
+        all_users: bool,
+        /// Show the changes without writing them
+        #[clap(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Args)]
+struct EditTarget {
+    /// App id, or the name of an installed app
+    app: String,
+    /// User id or persona name. Default: the user who logged in most recently
+    #[clap(long)]
+    user: Option<String>,
+}
+
+#[derive(Subcommand)]
+enum BackupCommand {
+    /// Lists backups, oldest first
+    List,
+
+    /// Restores a file from a backup. The current file is backed up first.
+    Restore {
+        /// Backup id as shown by `backup list`
+        id: String,
+    },
+
+    /// Removes old backups
+    Prune {
+        /// Number of most recent backups to keep
+        #[clap(long)]
+        keep: usize,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// Indented, human readable text
+    Text,
+    /// JSON. See the README for the schema
+    Json,
+    /// Comma separated values with a header row
+    Csv,
+    /// Tab separated values with a header row
+    Tsv,
+}
+
+fn print<T: Display + Serialize + Rows>(
+    value: &T,
+    format: Format,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match format {
+        Format::Text => println!("{}", value.to_string().trim_end()),
+        Format::Json => println!("{}", serde_json::to_string_pretty(value)?),
+        Format::Csv => delimited::write(&mut std::io::stdout().lock(), value, ',')?,
+        Format::Tsv => delimited::write(&mut std::io::stdout().lock(), value, '\t')?,
+    }
+
+    Ok(())
+}
+
+/// Edits the launch options of `target`'s app and prints the change
+fn edit(
+    steam_path: &Path,
+    target: &EditTarget,
+    edit: LaunchOptionsEdit,
+    wait: bool,
+    format: Format,
+) -> Result<(), Box<dyn std::error::Error>> {
+    ensure_steam_closed(steam_path, wait)?;
+    let change = edit_launch_options(
+        steam_path,
+        target.user.as_deref(),
+        &target.app,
+        &edit,
+        &BackupStore::open_default()?,
+    )?;
+
+    print(&change, format)
+}
+
+/// The installation named `name`, or the first one found
+fn select_install(
+    installs: Vec<SteamInstall>,
+    name: Option<&str>,
+) -> Result<SteamInstall, Box<dyn std::error::Error>> {
+    let names: Vec<&str> = installs.iter().map(|i| i.name.as_str()).collect();
+    let names = names.join(", ");
+    let install = match name {
+        Some(name) => installs
+            .into_iter()
+            .find(|i| i.name == name)
+            .ok_or_else(|| {
+                format!(
+                    "No {} Steam installation found. Found: {}",
+                    name,
+                    if names.is_empty() { "none" } else { &names }
+                )
+            })?,
+        None => installs
+            .into_iter()
+            .next()
+            .ok_or("Couldn't find a Steam installation. Use --steam-path to specify one.")?,
+    };
+    log::info!(
+        "Using {} Steam in '{}' (found: {})",
+        install.name,
+        install.root.display(),
+        names
+    );
+
+    Ok(install)
+}
+
+/// Steam home from --steam-path, or of the installation chosen with --install
+fn steam_path(opts: &Opts) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let repair = opts.command.as_ref().is_some_and(Command::writes);
+    match &opts.steam_path {
+        Some(path) => Ok(path.clone()),
+        None => Ok(select_install(locator::find_installs(repair), opts.install.as_deref())?.home),
+    }
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -36,19 +323,207 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         .init()
         .unwrap();
 
-    let steam_path = opts
-        .steam_path
-        .or_else(|| dirs::home_dir().map(|home| home.join(".steam")))
-        .ok_or("Couldn't find Steam directory")?;
+    if opts.all_installs {
+        let installs = locator::find_installs(false);
+        return match &opts.command {
+            None | Some(Command::Proton) => print(
+                &for_all_installs(installs, parse_tool_mapping)?,
+                opts.format,
+            ),
+            Some(Command::LaunchOptions {
+                parsed,
+                env,
+                wrapper,
+                command: None,
+            }) => {
+                let filter = LaunchOptionsFilter {
+                    env: env.clone(),
+                    wrapper: wrapper.clone(),
+                };
+                print(
+                    &for_all_installs(installs, |home| {
+                        parse_launch_options(home, *parsed, &filter)
+                    })?,
+                    opts.format,
+                )
+            }
+            Some(_) => {
+                Err("--all-installs only works with the proton and launch-options commands".into())
+            }
+        };
+    }
 
     match &opts.command {
         None | Some(Command::Proton) => {
+            let steam_path = steam_path(&opts)?;
             let config = parse_tool_mapping(&steam_path)?;
-            println!("{}", &config);
+            print(&config, opts.format)?;
+        }
+        Some(Command::LaunchOptions {
+            parsed,
+            env,
+            wrapper,
+            command,
+        }) => {
+            let steam_path = steam_path(&opts)?;
+            match command {
+                None => {
+                    let filter = LaunchOptionsFilter {
+                        env: env.clone(),
+                        wrapper: wrapper.clone(),
+                    };
+                    let config = parse_launch_options(&steam_path, *parsed, &filter)?;
+                    print(&config, opts.format)?;
+                }
+                Some(LaunchOptionsCommand::Stats) => {
+                    let stats = find_launch_options_stats(&steam_path)?;
+                    print(&stats, opts.format)?;
+                }
+                Some(LaunchOptionsCommand::Replace {
+                    env,
+                    regex,
+                    user,
+                    all_users,
+                    dry_run,
+                }) => {
+                    let action = match (env.as_deref(), regex.as_deref()) {
+                        (Some([from, to]), _) => ReplaceAction::env(from, to)?,
+                        (_, Some([pattern, replacement])) => {
+                            ReplaceAction::regex(pattern, replacement)?
+                        }
+                        _ => return Err("--env or --regex needs two values".into()),
+                    };
+                    if !dry_run {
+                        ensure_steam_closed(&steam_path, opts.wait)?;
+                    }
+                    let replacement = replace_launch_options(
+                        &steam_path,
+                        user.as_deref(),
+                        *all_users,
+                        &action,
+                        *dry_run,
+                        &BackupStore::open_default()?,
+                    )?;
+                    print(&replacement, opts.format)?;
+                }
+                Some(LaunchOptionsCommand::Set { target, options }) => edit(
+                    &steam_path,
+                    target,
+                    LaunchOptionsEdit::Set(options.to_string()),
+                    opts.wait,
+                    opts.format,
+                )?,
+                Some(LaunchOptionsCommand::AddEnv { target, var }) => edit(
+                    &steam_path,
+                    target,
+                    LaunchOptionsEdit::AddEnv(var.clone()),
+                    opts.wait,
+                    opts.format,
+                )?,
+                Some(LaunchOptionsCommand::RemoveEnv { target, name }) => edit(
+                    &steam_path,
+                    target,
+                    LaunchOptionsEdit::RemoveEnv(name.to_string()),
+                    opts.wait,
+                    opts.format,
+                )?,
+                Some(LaunchOptionsCommand::Clear { target }) => edit(
+                    &steam_path,
+                    target,
+                    LaunchOptionsEdit::Clear,
+                    opts.wait,
+                    opts.format,
+                )?,
+            }
+        }
+        Some(Command::Lint) => {
+            let steam_path = steam_path(&opts)?;
+            let report = lint_launch_options(&steam_path)?;
+            print(&report, opts.format)?;
+            if report.errors() > 0 {
+                std::process::exit(1);
+            }
+        }
+        Some(Command::Unused) => {
+            let steam_path = steam_path(&opts)?;
+            let tools = find_unused_tools(&steam_path)?;
+            print(&tools, opts.format)?;
+        }
+        Some(Command::Effective) => {
+            let steam_path = steam_path(&opts)?;
+            let tools = find_effective_tools(&steam_path)?;
+            print(&tools, opts.format)?;
+        }
+        Some(Command::ValveDefaults) => {
+            let steam_path = steam_path(&opts)?;
+            let defaults = find_valve_defaults(&steam_path)?;
+            print(&defaults, opts.format)?;
+        }
+        Some(Command::Prefixes) => {
+            let steam_path = steam_path(&opts)?;
+            let prefixes = find_prefixes(&steam_path)?;
+            print(&prefixes, opts.format)?;
+        }
+        Some(Command::Orphans { delete, dry_run }) => {
+            let steam_path = steam_path(&opts)?;
+            let action = match (delete, dry_run) {
+                (false, _) => OrphanAction::List,
+                (true, true) => OrphanAction::DryRun,
+                (true, false) => {
+                    ensure_steam_closed(&steam_path, opts.wait)?;
+                    OrphanAction::Delete
+                }
+            };
+            let orphans = find_orphans(&steam_path, action)?;
+            print(&orphans, opts.format)?;
+            if orphans.failed() > 0 {
+                return Err(format!("Couldn't delete {} directories", orphans.failed()).into());
+            }
+        }
+        Some(Command::Set { app, tool }) => {
+            let steam_path = steam_path(&opts)?;
+            ensure_steam_closed(&steam_path, opts.wait)?;
+            let change = set_tool(&steam_path, app, tool, &BackupStore::open_default()?)?;
+            print(&change, opts.format)?;
+        }
+        Some(Command::Migrate {
+            from,
+            to,
+            only_installed,
+            dry_run,
+        }) => {
+            let steam_path = steam_path(&opts)?;
+            if !dry_run {
+                ensure_steam_closed(&steam_path, opts.wait)?;
+            }
+            let migration = migrate_tool(
+                &steam_path,
+                from,
+                to,
+                *only_installed,
+                *dry_run,
+                &BackupStore::open_default()?,
+            )?;
+            print(&migration, opts.format)?;
+        }
+        Some(Command::Status) => {
+            let steam_path = steam_path(&opts)?;
+            let status = steam_status(&steam_path)?;
+            print(&status, opts.format)?;
+        }
+        Some(Command::Backup(BackupCommand::List)) => {
+            let backups = list_backups(&BackupStore::open_default()?)?;
+            print(&backups, opts.format)?;
+        }
+        Some(Command::Backup(BackupCommand::Restore { id })) => {
+            let steam_path = steam_path(&opts)?;
+            ensure_steam_closed(&steam_path, opts.wait)?;
+            let backup = restore_backup(&BackupStore::open_default()?, id)?;
+            print(&backup, opts.format)?;
         }
-        Some(Command::LaunchOptions) => {
-            let config = parse_launch_options(&steam_path)?;
-            println!("{}", &config);
+        Some(Command::Backup(BackupCommand::Prune { keep })) => {
+            let removed = prune_backups(&BackupStore::open_default()?, *keep)?;
+            print(&removed, opts.format)?;
         }
     };
 
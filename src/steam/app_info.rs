@@ -1,20 +1,471 @@
-use super::{parse_names_from_bin_vdf, AppId, Result};
+use super::bin_vdf::{parse_object, Reader};
+use super::vdf::{Object, Value};
+use super::{root_dir, AppId, Result};
 use log::error;
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
-const POSSIBLE_KEYS: [&str; 1] = ["name"];
+/// App whose key values hold Valve's Steam Play compatibility tool choices
+pub const STEAM_PLAY_MANIFEST_APP_ID: AppId = AppId(891390);
 
-pub fn parse_names(file_path: &Path, app_ids: &[&AppId]) -> Result<HashMap<AppId, String>> {
-    let mut result = HashMap::new();
+const MAGIC_V27: u32 = 0x07564427;
+const MAGIC_V28: u32 = 0x07564428;
+const MAGIC_V29: u32 = 0x07564429;
+
+/// A single app record from appinfo.vdf
+#[derive(Debug)]
+pub struct AppInfo {
+    pub app_id: AppId,
+    pub info_state: u32,
+    /// Unix timestamp
+    pub last_updated: u32,
+    pub pics_token: u64,
+    pub sha1: [u8; 20],
+    pub change_number: u32,
+    /// Hash of the binary key values. Only present in version 28 and later.
+    pub binary_data_sha1: Option<[u8; 20]>,
+    pub data: Object,
+}
+
+impl AppInfo {
+    fn common(&self, key: &str) -> Option<&str> {
+        self.data
+            .get_path(&["appinfo", "common", key])
+            .and_then(Value::as_str)
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.common("name")
+    }
+
+    /// Game, Application, Tool, Config, etc.
+    pub fn app_type(&self) -> Option<&str> {
+        self.common("type")
+    }
+
+    /// Comma separated platforms, e.g. `windows,linux`
+    pub fn os_list(&self) -> Option<&str> {
+        self.common("oslist")
+    }
+
+    pub fn supports_linux(&self) -> bool {
+        self.os_list()
+            .is_some_and(|list| list.split(',').any(|os| os.trim() == "linux"))
+    }
+}
+
+/// A tool Valve picked for an app
+#[derive(Clone, Debug, PartialEq)]
+pub struct AppMapping {
+    /// Internal tool name, e.g. `proton_8`
+    pub tool: String,
+    pub config: Option<String>,
+    pub comment: Option<String>,
+}
+
+/// One of Valve's compatibility tools
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValveCompatTool {
+    /// App the tool is installed as
+    pub app_id: Option<AppId>,
+    pub display_name: Option<String>,
+    /// Other names the tool is known by, e.g. `proton-8.0`
+    pub aliases: Vec<String>,
+    /// Platform the tool runs apps for, e.g. `windows`
+    pub from_os_list: Option<String>,
+    /// Platform the tool runs on, e.g. `linux`
+    pub to_os_list: Option<String>,
+}
+
+/// Valve's compatibility tools and the tools it picked for specific apps, from the key values
+/// of app 891390
+/// ```vdf
+/// "appinfo"
+/// {
+///     "extended"
+///     {
+///         "app_mappings"
+///         {
+///             "[app_id]"
+///             {
+///                 "appid"     "[app_id]"
+///                 "tool"      "[tool]"
+///                 "config"    "[config]"
+///                 "comment"   "[comment]"
+///             }
+///         }
+///         "compat_tools"
+///         {
+///             "[tool]"
+///             {
+///                 "appid"         "[app_id]"
+///                 "display_name"  "[display_name]"
+///                 "aliases"       "[alias1],[alias2]"
+///                 "from_oslist"   "windows"
+///                 "to_oslist"     "linux"
+///             }
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct SteamPlayManifest {
+    pub app_mappings: HashMap<AppId, AppMapping>,
+    pub compat_tools: HashMap<String, ValveCompatTool>,
+}
+
+/// Children of an `extended` section that are objects
+fn extended_objects<'a>(
+    app: &'a AppInfo,
+    section: &str,
+) -> impl Iterator<Item = (&'a str, &'a Object)> {
+    app.data
+        .get_path(&["appinfo", "extended", section])
+        .and_then(Value::as_object)
+        .into_iter()
+        .flat_map(|section| section.iter())
+        .filter_map(|(key, value)| Some((key, value.as_object()?)))
+}
+
+fn non_empty_string(object: &Object, key: &str) -> Option<String> {
+    object
+        .get(key)
+        .and_then(Value::as_str)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+}
+
+impl SteamPlayManifest {
+    pub fn from_app_info(app: &AppInfo) -> Self {
+        let app_mappings = extended_objects(app, "app_mappings")
+            .filter_map(|(id, mapping)| {
+                let app_mapping = AppMapping {
+                    tool: non_empty_string(mapping, "tool")?,
+                    config: non_empty_string(mapping, "config"),
+                    comment: non_empty_string(mapping, "comment"),
+                };
+                Some((id.parse().ok()?, app_mapping))
+            })
+            .collect();
+        let compat_tools = extended_objects(app, "compat_tools")
+            .map(|(name, tool)| {
+                let compat_tool = ValveCompatTool {
+                    app_id: tool.get("appid").and_then(Value::as_u64).map(AppId::new),
+                    display_name: non_empty_string(tool, "display_name"),
+                    aliases: non_empty_string(tool, "aliases")
+                        .map(|aliases| {
+                            aliases
+                                .split([',', ' '])
+                                .filter(|alias| !alias.is_empty())
+                                .map(|alias| alias.to_string())
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                    from_os_list: non_empty_string(tool, "from_oslist"),
+                    to_os_list: non_empty_string(tool, "to_oslist"),
+                };
+                (name.to_string(), compat_tool)
+            })
+            .collect();
+
+        Self {
+            app_mappings,
+            compat_tools,
+        }
+    }
+}
+
+/// Reads the string table that version 29 and later use for binary key names
+/// ```text
+/// [u32 count] "key1\0" "key2\0" ...
+/// ```
+fn parse_string_table(contents: &[u8], offset: u64) -> Result<Vec<String>> {
+    let table = usize::try_from(offset)
+        .ok()
+        .and_then(|offset| contents.get(offset..))
+        .ok_or_else(|| format!("String table offset {} is out of range", offset))?;
+    let mut reader = Reader::new(table);
+    let count = reader.u32()?;
+
+    (0..count).map(|_| reader.c_str()).collect()
+}
+
+/// Parses appinfo.vdf. Key values are only parsed for apps where `include` returns true.
+/// ```text
+/// [u32 magic] [u32 universe] ([i64 string table offset] v29+)
+/// [u32 app id] [u32 size] [u32 info state] [u32 last updated] [u64 pics token] [20 sha1]
+///     [u32 change number] ([20 binary data sha1] v28+) [binary key values]
+/// ...
+/// [u32 0]
+/// ```
+pub fn parse_app_info(contents: &[u8], include: impl Fn(&AppId) -> bool) -> Result<Vec<AppInfo>> {
+    let mut reader = Reader::new(contents);
+    let magic = reader.u32()?;
+    let _universe = reader.u32()?;
+    let string_table = match magic {
+        MAGIC_V27 | MAGIC_V28 => None,
+        MAGIC_V29 => Some(parse_string_table(contents, reader.u64()?)?),
+        _ => return Err(format!("Unsupported appinfo.vdf version {:#010x}", magic).into()),
+    };
+
+    let mut apps = Vec::new();
+    loop {
+        let app_id = AppId::new(reader.u32()? as u64);
+        if app_id.0 == 0 {
+            break;
+        }
+
+        let size = reader.u32()? as usize;
+        if !include(&app_id) {
+            reader.bytes(size)?;
+            continue;
+        }
+
+        let mut record = Reader::new(reader.bytes(size)?);
+        apps.push(AppInfo {
+            app_id,
+            info_state: record.u32()?,
+            last_updated: record.u32()?,
+            pics_token: record.u64()?,
+            sha1: record.sha1()?,
+            change_number: record.u32()?,
+            binary_data_sha1: match magic {
+                MAGIC_V27 => None,
+                _ => Some(record.sha1()?),
+            },
+            data: parse_object(&mut record, string_table.as_deref())?,
+        });
+    }
+
+    Ok(apps)
+}
+
+pub fn app_info_path(steam_home: &Path) -> PathBuf {
+    root_dir(steam_home).join("appcache/appinfo.vdf")
+}
 
+/// The Steam Play manifest from appinfo.vdf. `None` if it couldn't be read.
+pub fn read_steam_play_manifest(steam_home: &Path) -> Option<SteamPlayManifest> {
+    read_app_info(&app_info_path(steam_home), |id| {
+        id == &STEAM_PLAY_MANIFEST_APP_ID
+    })
+    .first()
+    .map(SteamPlayManifest::from_app_info)
+}
+
+/// Reads and parses appinfo.vdf, logging any errors and returning no apps instead
+pub fn read_app_info(file_path: &Path, include: impl Fn(&AppId) -> bool) -> Vec<AppInfo> {
     match std::fs::read(file_path) {
-        Ok(contents) => {
-            let names = parse_names_from_bin_vdf(&contents, &POSSIBLE_KEYS, app_ids);
-            result.extend(names);
+        Ok(contents) => match parse_app_info(&contents, include) {
+            Ok(apps) => apps,
+            Err(e) => {
+                error!("Failed to parse '{}': {}", file_path.display(), e);
+                Vec::new()
+            }
+        },
+        Err(e) => {
+            error!("Failed to read '{}': {}", file_path.display(), e);
+            Vec::new()
         }
-        Err(e) => error!("Failed to read '{}': {}", file_path.display(), e),
     }
+}
+
+pub fn parse_names(file_path: &Path, app_ids: &[&AppId]) -> Result<HashMap<AppId, String>> {
+    let whitelist: HashSet<&AppId> = app_ids.iter().copied().collect();
+    let result = read_app_info(file_path, |id| whitelist.contains(id))
+        .iter()
+        .filter_map(|app| app.name().map(|name| (app.app_id, name.to_string())))
+        .collect();
 
     Ok(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str, string_table: &mut Option<Vec<String>>) -> Vec<u8> {
+        match string_table {
+            Some(table) => {
+                table.push(name.to_string());
+                ((table.len() - 1) as u32).to_le_bytes().to_vec()
+            }
+            None => [name.as_bytes(), b"\0"].concat(),
+        }
+    }
+
+    fn app_record(
+        id: u32,
+        name: &str,
+        magic: u32,
+        string_table: &mut Option<Vec<String>>,
+    ) -> Vec<u8> {
+        let mut kv = vec![0x00];
+        kv.extend(key("appinfo", string_table));
+        kv.push(0x02);
+        kv.extend(key("appid", string_table));
+        kv.extend(id.to_le_bytes());
+        kv.push(0x00);
+        kv.extend(key("common", string_table));
+        kv.push(0x01);
+        kv.extend(key("name", string_table));
+        kv.extend(name.as_bytes());
+        kv.extend([0x00, 0x08, 0x08, 0x08]);
+
+        let mut record = Vec::new();
+        record.extend(1u32.to_le_bytes()); // info state
+        record.extend(1637000000u32.to_le_bytes()); // last updated
+        record.extend(42u64.to_le_bytes()); // pics token
+        record.extend([0xAA; 20]); // sha1
+        record.extend(7u32.to_le_bytes()); // change number
+        if magic != MAGIC_V27 {
+            record.extend([0xBB; 20]); // binary data sha1
+        }
+        record.extend(kv);
+
+        let mut result = id.to_le_bytes().to_vec();
+        result.extend((record.len() as u32).to_le_bytes());
+        result.extend(record);
+        result
+    }
+
+    fn app_info_file(magic: u32, apps: &[(u32, &str)]) -> Vec<u8> {
+        let mut string_table = (magic == MAGIC_V29).then(Vec::new);
+        let records: Vec<u8> = apps
+            .iter()
+            .flat_map(|(id, name)| app_record(*id, name, magic, &mut string_table))
+            .collect();
+
+        let mut contents = magic.to_le_bytes().to_vec();
+        contents.extend(1u32.to_le_bytes()); // universe
+        let header_len = contents.len() + string_table.as_ref().map_or(0, |_| 8);
+        let table_offset = (header_len + records.len() + 4) as u64;
+        if string_table.is_some() {
+            contents.extend(table_offset.to_le_bytes());
+        }
+        contents.extend(records);
+        contents.extend(0u32.to_le_bytes());
+        if let Some(table) = string_table {
+            contents.extend((table.len() as u32).to_le_bytes());
+            for s in table {
+                contents.extend(s.as_bytes());
+                contents.push(0);
+            }
+        }
+
+        contents
+    }
+
+    #[test]
+    fn parses_records_for_all_versions() {
+        for magic in [MAGIC_V27, MAGIC_V28, MAGIC_V29] {
+            let contents = app_info_file(magic, &[(10, "Name1"), (20, "Name2")]);
+
+            let apps = parse_app_info(&contents, |_| true).unwrap();
+
+            assert_eq!(apps.len(), 2, "version {:#x}", magic);
+            assert_eq!(apps[0].app_id, AppId::new(10));
+            assert_eq!(apps[0].name(), Some("Name1"));
+            assert_eq!(apps[0].last_updated, 1637000000);
+            assert_eq!(apps[0].pics_token, 42);
+            assert_eq!(apps[0].change_number, 7);
+            assert_eq!(apps[1].name(), Some("Name2"));
+            assert_eq!(apps[1].binary_data_sha1.is_some(), magic != MAGIC_V27);
+        }
+    }
+
+    #[test]
+    fn skips_excluded_apps() {
+        let contents = app_info_file(MAGIC_V28, &[(10, "Name1"), (20, "Name2")]);
+
+        let apps = parse_app_info(&contents, |id| id == &AppId::new(20)).unwrap();
+
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].name(), Some("Name2"));
+    }
+
+    #[test]
+    fn errors_on_unknown_version() {
+        let contents = app_info_file(0x07564426, &[(10, "Name1")]);
+
+        assert!(parse_app_info(&contents, |_| true).is_err());
+    }
+
+    fn app_info(id: u64, data: &str) -> AppInfo {
+        AppInfo {
+            app_id: AppId::new(id),
+            info_state: 2,
+            last_updated: 0,
+            pics_token: 0,
+            sha1: [0; 20],
+            change_number: 0,
+            binary_data_sha1: None,
+            data: crate::steam::vdf::parse(data).unwrap(),
+        }
+    }
+
+    #[test]
+    fn reads_platforms() {
+        let native = app_info(
+            620,
+            r#""appinfo" { "common" { "oslist" "windows,macos,linux" } }"#,
+        );
+        let windows = app_info(1245620, r#""appinfo" { "common" { "oslist" "windows" } }"#);
+
+        assert!(native.supports_linux());
+        assert!(!windows.supports_linux());
+    }
+
+    #[test]
+    fn parses_steam_play_manifest() {
+        let app = app_info(
+            891390,
+            r#"
+            "appinfo"
+            {
+                "extended"
+                {
+                    "app_mappings"
+                    {
+                        "1245620"
+                        {
+                            "appid"     "1245620"
+                            "tool"      "proton_8"
+                            "config"    ""
+                            "comment"   "Elden Ring"
+                        }
+                        "620"   { "appid" "620" }
+                    }
+                    "compat_tools"
+                    {
+                        "proton_8"
+                        {
+                            "appid"         "2348590"
+                            "display_name"  "Proton 8.0"
+                            "aliases"       "proton-8.0,proton8"
+                            "from_oslist"   "windows"
+                            "to_oslist"     "linux"
+                        }
+                    }
+                }
+            }"#,
+        );
+
+        let manifest = SteamPlayManifest::from_app_info(&app);
+
+        assert_eq!(manifest.app_mappings.len(), 1);
+        assert_eq!(
+            manifest.app_mappings.get(&AppId::new(1245620)),
+            Some(&AppMapping {
+                tool: "proton_8".to_string(),
+                config: None,
+                comment: Some("Elden Ring".to_string()),
+            })
+        );
+        let proton = &manifest.compat_tools["proton_8"];
+        assert_eq!(proton.app_id, Some(AppId::new(2348590)));
+        assert_eq!(proton.display_name.as_deref(), Some("Proton 8.0"));
+        assert_eq!(proton.aliases, ["proton-8.0", "proton8"]);
+        assert_eq!(proton.from_os_list.as_deref(), Some("windows"));
+    }
+}
@@ -0,0 +1,243 @@
+use super::vdf::{self, Value};
+use super::{AppId, Result};
+use crate::read_text_config;
+use log::warn;
+use serde::Serialize;
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+
+/// `StateFlags` of an app manifest
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct StateFlags(pub u32);
+
+impl StateFlags {
+    const NAMES: [(u32, &'static str); 23] = [
+        (1, "Uninstalled"),
+        (2, "Update Required"),
+        (4, "Fully Installed"),
+        (8, "Encrypted"),
+        (16, "Locked"),
+        (32, "Files Missing"),
+        (64, "App Running"),
+        (128, "Files Corrupt"),
+        (256, "Update Running"),
+        (512, "Update Paused"),
+        (1024, "Update Started"),
+        (2048, "Uninstalling"),
+        (4096, "Backup Running"),
+        (65536, "Reconfiguring"),
+        (131072, "Validating"),
+        (262144, "Adding Files"),
+        (524288, "Preallocating"),
+        (1048576, "Downloading"),
+        (2097152, "Staging"),
+        (4194304, "Committing"),
+        (8388608, "Update Stopping"),
+        (16777216, "Update Queued"),
+        (33554432, "Uninstall Queued"),
+    ];
+
+    pub fn is_fully_installed(&self) -> bool {
+        self.0 & 4 != 0
+    }
+
+    pub fn update_required(&self) -> bool {
+        self.0 & 2 != 0
+    }
+
+    pub fn files_missing(&self) -> bool {
+        self.0 & (32 | 128) != 0
+    }
+
+    /// Uninstalled, uninstalling or queued to be
+    pub fn uninstalling(&self) -> bool {
+        self.0 & (1 | 2048 | 33554432) != 0
+    }
+
+    /// Whether the app's files are on disk. Apps that need or are in the middle of an update
+    /// are still installed, even though they aren't fully installed.
+    pub fn is_installed(&self) -> bool {
+        self.0 != 0 && !self.uninstalling() && !self.files_missing()
+    }
+}
+
+impl Display for StateFlags {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = Self::NAMES
+            .iter()
+            .filter(|(flag, _)| self.0 & flag != 0)
+            .map(|(_, name)| *name)
+            .collect();
+
+        if names.is_empty() {
+            write!(f, "Invalid")
+        } else {
+            write!(f, "{}", names.join(", "))
+        }
+    }
+}
+
+/// An app's `steamapps/appmanifest_[app_id].acf`
+#[derive(Clone, Debug)]
+pub struct AppManifest {
+    pub app_id: AppId,
+    pub name: String,
+    /// Directory name under `steamapps/common`
+    pub install_dir: String,
+    pub state_flags: StateFlags,
+    /// Size on disk in bytes
+    pub size_on_disk: u64,
+    /// Library folder containing the manifest's `steamapps` directory
+    pub library_path: PathBuf,
+}
+
+impl AppManifest {
+    pub fn install_path(&self) -> PathBuf {
+        self.library_path
+            .join("steamapps/common")
+            .join(&self.install_dir)
+    }
+}
+
+/// ```vdf
+/// "AppState"
+/// {
+///     "appid"         "[app_id]"
+///     "name"          "[name]"
+///     "StateFlags"    "[state_flags]"
+///     "installdir"    "[install_dir]"
+///     "SizeOnDisk"    "[size_on_disk]"
+///     ...
+/// }
+/// ```
+pub fn parse_app_manifest(contents: &str, library_path: &Path) -> Result<AppManifest> {
+    let root = vdf::parse(contents)?;
+    let state = root
+        .get_object("AppState")
+        .ok_or("Missing 'AppState' section")?;
+    let string = |key| {
+        state
+            .get(key)
+            .and_then(Value::as_str)
+            .map(|s| s.to_string())
+            .ok_or_else(|| format!("Missing '{}' key", key))
+    };
+    let number = |key| state.get(key).and_then(Value::as_u64).unwrap_or(0);
+
+    Ok(AppManifest {
+        app_id: string("appid")?.parse()?,
+        name: string("name")?,
+        install_dir: string("installdir")?,
+        state_flags: StateFlags(number("StateFlags") as u32),
+        size_on_disk: number("SizeOnDisk"),
+        library_path: library_path.to_path_buf(),
+    })
+}
+
+/// Paths of all app manifests in a `steamapps` directory
+pub fn find_app_manifests(steamapps_dir: &Path) -> Vec<PathBuf> {
+    std::fs::read_dir(steamapps_dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("appmanifest_") && name.ends_with(".acf"))
+        })
+        .collect()
+}
+
+/// App ids of every manifest in a library, taken from the file names so manifests that
+/// don't parse are still counted
+pub fn find_app_manifest_ids(library_path: &Path) -> Vec<AppId> {
+    find_app_manifests(&library_path.join("steamapps"))
+        .iter()
+        .filter_map(|path| path.file_stem()?.to_str()?.strip_prefix("appmanifest_"))
+        .filter_map(|id| id.parse().ok())
+        .collect()
+}
+
+/// Parses all app manifests in a library, skipping any that are malformed
+pub fn parse_app_manifests(library_path: &Path) -> Vec<AppManifest> {
+    find_app_manifests(&library_path.join("steamapps"))
+        .into_iter()
+        .filter_map(|path| {
+            match read_text_config(&path).and_then(|c| parse_app_manifest(&c, library_path)) {
+                Ok(manifest) => Some(manifest),
+                Err(e) => {
+                    warn!("Couldn't parse app manifest '{}': {}", path.display(), e);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_app_manifest() {
+        let contents = r#"
+            "AppState"
+            {
+                "appid"		"2348590"
+                "Universe"		"1"
+                "name"		"Proton 8.0"
+                "StateFlags"		"6"
+                "installdir"		"Proton 8.0"
+                "SizeOnDisk"		"1234567"
+            }"#;
+        let library = Path::new("/games");
+
+        let manifest = parse_app_manifest(contents, library).unwrap();
+
+        assert_eq!(manifest.app_id, AppId::new(2348590));
+        assert_eq!(manifest.name, "Proton 8.0");
+        assert_eq!(manifest.install_dir, "Proton 8.0");
+        assert_eq!(manifest.size_on_disk, 1234567);
+        assert!(manifest.state_flags.is_fully_installed());
+        assert!(manifest.state_flags.update_required());
+        assert_eq!(
+            manifest.install_path(),
+            Path::new("/games/steamapps/common/Proton 8.0")
+        );
+    }
+
+    #[test]
+    fn errors_if_app_id_missing() {
+        let contents = r#""AppState" { "name" "Proton 8.0" "installdir" "Proton 8.0" }"#;
+
+        assert!(parse_app_manifest(contents, Path::new("/")).is_err());
+    }
+
+    #[test]
+    fn state_flags_display_all_names() {
+        assert_eq!(
+            StateFlags(6).to_string(),
+            "Update Required, Fully Installed"
+        );
+        assert_eq!(StateFlags(0).to_string(), "Invalid");
+    }
+
+    #[test]
+    fn apps_needing_an_update_are_installed() {
+        // Update required, fully installed and update started + update required
+        for flags in [2, 6, 1026] {
+            assert!(StateFlags(flags).is_installed(), "{}", flags);
+        }
+        assert!(StateFlags(2).update_required());
+        assert!(!StateFlags(2).is_fully_installed());
+    }
+
+    #[test]
+    fn apps_being_removed_or_missing_files_are_not_installed() {
+        for flags in [0, 1, 4 | 32, 4 | 128, 4 | 2048, 4 | 33554432] {
+            assert!(!StateFlags(flags).is_installed(), "{}", flags);
+        }
+    }
+}
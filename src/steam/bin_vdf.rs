@@ -0,0 +1,212 @@
+//! Binary KeyValues parsing, as used by appinfo.vdf and shortcuts.vdf.
+//!
+//! Each entry is a type byte, a key and a value. Objects end with an [END] byte.
+//! ```text
+//! [OBJECT] "shortcuts\0"
+//!     [OBJECT] "0\0"
+//!         [INT32]  "appid\0"   [u32 le]
+//!         [STRING] "AppName\0" "The Name\0"
+//!     [END]
+//! [END]
+//! ```
+use super::vdf::{Entry, Object, Value};
+use super::Result;
+
+const OBJECT: u8 = 0x00;
+const STRING: u8 = 0x01;
+const INT32: u8 = 0x02;
+const FLOAT: u8 = 0x03;
+const POINTER: u8 = 0x04;
+const WIDE_STRING: u8 = 0x05;
+const COLOR: u8 = 0x06;
+const UINT64: u8 = 0x07;
+const END: u8 = 0x08;
+const INT64: u8 = 0x0A;
+const ALT_END: u8 = 0x0B;
+
+/// Cursor over little endian binary data
+pub(super) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| format!("Unexpected end of data at offset {}", self.pos))?;
+        self.pos += len;
+
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes(4)?.try_into()?))
+    }
+
+    pub fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes(8)?.try_into()?))
+    }
+
+    pub fn sha1(&mut self) -> Result<[u8; 20]> {
+        Ok(self.bytes(20)?.try_into()?)
+    }
+
+    /// Null terminated UTF-8 string
+    pub fn c_str(&mut self) -> Result<String> {
+        let rest = &self.data[self.pos.min(self.data.len())..];
+        let len = rest
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| format!("Unterminated string at offset {}", self.pos))?;
+        self.pos += len + 1;
+
+        Ok(String::from_utf8_lossy(&rest[..len]).into_owned())
+    }
+
+    /// Null terminated UTF-16 string
+    fn wide_str(&mut self) -> Result<String> {
+        let mut units = Vec::new();
+        loop {
+            match u16::from_le_bytes(self.bytes(2)?.try_into()?) {
+                0 => break,
+                unit => units.push(unit),
+            }
+        }
+
+        Ok(String::from_utf16_lossy(&units))
+    }
+}
+
+/// Parses entries until the end of the current object. Keys are read from `string_table`
+/// by index when present, otherwise they're inline null terminated strings.
+pub(super) fn parse_object(reader: &mut Reader, string_table: Option<&[String]>) -> Result<Object> {
+    let mut entries = Vec::new();
+    while !reader.is_empty() {
+        let value_type = reader.u8()?;
+        if value_type == END || value_type == ALT_END {
+            break;
+        }
+
+        let key = match string_table {
+            Some(table) => {
+                let index = reader.u32()? as usize;
+                table
+                    .get(index)
+                    .cloned()
+                    .ok_or_else(|| format!("String table index {} is out of range", index))?
+            }
+            None => reader.c_str()?,
+        };
+        let value = match value_type {
+            OBJECT => Value::Object(parse_object(reader, string_table)?),
+            STRING => Value::String(reader.c_str()?),
+            WIDE_STRING => Value::String(reader.wide_str()?),
+            INT32 | POINTER | COLOR => Value::Int32(reader.u32()? as i32),
+            FLOAT => Value::Float(f32::from_bits(reader.u32()?)),
+            UINT64 => Value::UInt64(reader.u64()?),
+            INT64 => Value::Int64(reader.u64()? as i64),
+            t => {
+                return Err(format!(
+                    "Unknown value type {:#04x} for key '{}' at offset {}",
+                    t,
+                    key,
+                    reader.position() - 1
+                )
+                .into())
+            }
+        };
+
+        entries.push(Entry {
+            key,
+            value,
+            span: None,
+        });
+    }
+
+    Ok(Object::from(entries))
+}
+
+/// Parses the contents of a binary VDF file into its root object
+pub fn parse(data: &[u8]) -> Result<Object> {
+    parse_object(&mut Reader::new(data), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_typed_values() {
+        let mut data = vec![OBJECT];
+        data.extend(b"root\0");
+        data.push(STRING);
+        data.extend(b"name\0The Name\0");
+        data.push(INT32);
+        data.extend(b"appid\0");
+        data.extend(2583605614u32.to_le_bytes());
+        data.push(UINT64);
+        data.extend(b"big\0");
+        data.extend(u64::MAX.to_le_bytes());
+        data.extend([END, END]);
+
+        let root = parse(&data).unwrap();
+        let obj = root.get_object("root").unwrap();
+
+        assert_eq!(obj.get("name").and_then(Value::as_str), Some("The Name"));
+        assert_eq!(obj.get("appid").and_then(Value::as_u64), Some(2583605614));
+        assert_eq!(obj.get("big"), Some(&Value::UInt64(u64::MAX)));
+    }
+
+    #[test]
+    fn reads_keys_from_string_table() {
+        let table = ["common".to_string(), "name".to_string()];
+        let mut data = vec![OBJECT];
+        data.extend(0u32.to_le_bytes());
+        data.push(STRING);
+        data.extend(1u32.to_le_bytes());
+        data.extend(b"The Name\0");
+        data.extend([END, END]);
+
+        let root = parse_object(&mut Reader::new(&data), Some(&table)).unwrap();
+
+        assert_eq!(
+            root.get_path(&["common", "name"]).and_then(Value::as_str),
+            Some("The Name")
+        );
+    }
+
+    #[test]
+    fn errors_on_unknown_type() {
+        let mut data = vec![0x42];
+        data.extend(b"key\0");
+
+        assert!(parse(&data).is_err());
+    }
+
+    #[test]
+    fn errors_on_truncated_data() {
+        let mut data = vec![INT32];
+        data.extend(b"key\0");
+        data.extend([0x01, 0x02]);
+
+        assert!(parse(&data).is_err());
+    }
+}
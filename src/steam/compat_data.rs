@@ -0,0 +1,209 @@
+//! Wine prefixes that compatibility tools create for each app
+//! ```text
+//! [library]/steamapps/compatdata/[app_id]/
+//!     version     [prefix version]
+//!     pfx/
+//! ```
+use super::installed_tools::{version_numbers, InstalledTool, ToolFamily};
+use super::AppId;
+use serde::Serialize;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Debug)]
+pub struct CompatData {
+    pub app_id: AppId,
+    pub path: PathBuf,
+    /// Version of the tool that created or last upgraded the prefix
+    pub version: Option<String>,
+    /// When the prefix was last used. Wine updates `pfx/user.reg` whenever the app runs.
+    pub modified: Option<SystemTime>,
+}
+
+fn parse_prefix_version(contents: &str) -> Option<String> {
+    let version = contents.trim();
+    (!version.is_empty()).then(|| version.to_string())
+}
+
+/// Directories under `steamapps/[dir_name]` that are named after an app id
+pub fn find_app_dirs(library: &Path, dir_name: &str) -> Vec<(AppId, PathBuf)> {
+    fs::read_dir(library.join("steamapps").join(dir_name))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| {
+            let app_id = entry.file_name().to_str()?.parse().ok()?;
+            Some((app_id, entry.path()))
+        })
+        .collect()
+}
+
+/// Every prefix in a library's `steamapps/compatdata`
+pub fn find_compat_data(library: &Path) -> Vec<CompatData> {
+    find_app_dirs(library, "compatdata")
+        .into_iter()
+        .map(|(app_id, path)| {
+            let version = fs::read_to_string(path.join("version"))
+                .ok()
+                .and_then(|v| parse_prefix_version(&v));
+            let modified = [path.join("pfx/user.reg"), path.clone()]
+                .iter()
+                .find_map(|p| fs::metadata(p).and_then(|m| m.modified()).ok());
+
+            CompatData {
+                app_id,
+                path,
+                version,
+                modified,
+            }
+        })
+        .collect()
+}
+
+/// Problems running a prefix with a tool other than the one that last upgraded it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrefixWarning {
+    /// The tool is older than the one that last upgraded the prefix, which may break it
+    ToolOlder,
+    /// The prefix was created by a tool from a different family
+    DifferentFamily,
+}
+
+impl Display for PrefixWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PrefixWarning::ToolOlder => write!(f, "tool older"),
+            PrefixWarning::DifferentFamily => write!(f, "different family"),
+        }
+    }
+}
+
+/// Family of the tool that wrote `version` to a prefix. A tool that writes exactly that
+/// version is preferred, falling back to guessing from the version's format.
+pub fn prefix_family(
+    version: &str,
+    tool: &InstalledTool,
+    installed_tools: &[InstalledTool],
+) -> (ToolFamily, bool) {
+    let writers: Vec<ToolFamily> = installed_tools
+        .iter()
+        .filter(|t| t.prefix_version.as_deref() == Some(version))
+        .map(|t| t.family())
+        .collect();
+
+    if writers.contains(&tool.family()) {
+        (tool.family(), true)
+    } else if let Some(family) = writers.first() {
+        (*family, true)
+    } else {
+        (ToolFamily::from_prefix_version(version), false)
+    }
+}
+
+/// Compares a prefix's version with the tool its app is mapped to
+pub fn check_prefix(
+    version: &str,
+    tool: &InstalledTool,
+    installed_tools: &[InstalledTool],
+) -> (ToolFamily, Vec<PrefixWarning>) {
+    let (family, known) = prefix_family(version, tool, installed_tools);
+    let tool_family = tool.family();
+    // Experimental versions look like Valve's, so only a known writer can tell them apart
+    let compatible = family == tool_family
+        || family == ToolFamily::Other
+        || tool_family == ToolFamily::Other
+        || (!known && family == ToolFamily::Valve && tool_family == ToolFamily::Experimental);
+    let mut warnings = Vec::new();
+
+    if !compatible {
+        warnings.push(PrefixWarning::DifferentFamily);
+    }
+
+    if let Some(expected) = &tool.prefix_version {
+        let prefix = version_numbers(version);
+        let expected = version_numbers(expected);
+        // Build numbers of different families aren't comparable, but major versions are
+        let older = if prefix.is_empty() || expected.is_empty() {
+            false
+        } else if compatible {
+            expected < prefix
+        } else {
+            expected[0] < prefix[0]
+        };
+        if older {
+            warnings.push(PrefixWarning::ToolOlder);
+        }
+    }
+
+    (family, warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::installed_tools::ToolSource;
+    use super::*;
+
+    fn tool(name: &str, prefix_version: &str) -> InstalledTool {
+        InstalledTool {
+            name: name.to_string(),
+            display_name: name.to_string(),
+            version: None,
+            prefix_version: Some(prefix_version.to_string()),
+            install_path: PathBuf::new(),
+            source: ToolSource::Custom,
+        }
+    }
+
+    #[test]
+    fn prefix_version_is_trimmed() {
+        assert_eq!(
+            parse_prefix_version("GE-Proton8-25\n"),
+            Some("GE-Proton8-25".to_string())
+        );
+        assert_eq!(parse_prefix_version(" \n"), None);
+    }
+
+    #[test]
+    fn warns_when_tool_is_older_than_prefix() {
+        let proton_7 = tool("proton_7", "7.0-6");
+        let tools = [proton_7.clone(), tool("proton_8", "8.0-105")];
+
+        assert_eq!(
+            check_prefix("8.0-105", &proton_7, &tools),
+            (ToolFamily::Valve, vec![PrefixWarning::ToolOlder])
+        );
+        assert_eq!(
+            check_prefix("6.3-8", &proton_7, &tools),
+            (ToolFamily::Valve, vec![])
+        );
+    }
+
+    #[test]
+    fn warns_when_families_differ() {
+        let ge = tool("GE-Proton8-25", "GE-Proton8-25");
+        let proton_8 = tool("proton_8", "8.0-105");
+        let experimental = tool("proton_experimental", "9.0-200");
+        let tools = [ge.clone(), proton_8.clone(), experimental.clone()];
+
+        assert_eq!(
+            check_prefix("GE-Proton8-25", &proton_8, &tools),
+            (ToolFamily::Ge, vec![PrefixWarning::DifferentFamily])
+        );
+        assert_eq!(
+            check_prefix("9.0-200", &proton_8, &tools),
+            (
+                ToolFamily::Experimental,
+                vec![PrefixWarning::DifferentFamily, PrefixWarning::ToolOlder]
+            )
+        );
+        // Could have been written by an Experimental build that's since been updated
+        assert_eq!(
+            check_prefix("8.0-104", &experimental, &tools),
+            (ToolFamily::Valve, vec![])
+        );
+    }
+}
@@ -1,35 +1,94 @@
-use super::{parse_vdf_keys, AppId, KeyParser, DEFAULT_PROTON_APP_ID};
-use derive_more::{From, Into, IntoIterator};
+use super::vdf_edit::Document;
+use super::{parse_vdf_keys, vdf, AppId, KeyParser, Result, DEFAULT_PROTON_APP_ID};
+use derive_more::IntoIterator;
 use std::collections::hash_map::{Entry, Values};
 use std::collections::{HashMap, HashSet};
 
-#[derive(IntoIterator, Into, From, Default)]
-pub struct CompatToolMapping(HashMap<String, Vec<AppId>>);
+#[derive(IntoIterator, Default)]
+pub struct CompatToolMapping {
+    #[into_iterator(owned)]
+    tools: HashMap<String, Vec<AppId>>,
+    /// Tool used for all other titles, stored as app 0
+    pub default_tool: Option<String>,
+}
 impl CompatToolMapping {
     pub fn values(&self) -> Values<'_, String, Vec<AppId>> {
-        self.0.values()
+        self.tools.values()
     }
     pub fn entry(&mut self, key: String) -> Entry<'_, String, Vec<AppId>> {
-        self.0.entry(key)
+        self.tools.entry(key)
     }
     pub fn new() -> Self {
-        Self(HashMap::new())
+        Self::default()
     }
     pub fn apps(&self) -> HashSet<&AppId> {
-        self.0.values().flatten().collect()
+        self.tools.values().flatten().collect()
+    }
+    /// Whether any app, not counting the default, is mapped to `name`
+    pub fn contains_tool(&self, name: &str) -> bool {
+        self.tools.contains_key(name)
+    }
+    /// Whether any app is mapped to `name` or it's the default tool
+    pub fn uses_tool(&self, name: &str) -> bool {
+        self.contains_tool(name) || self.default_tool.as_deref() == Some(name)
+    }
+    pub fn apps_for(&self, tool: &str) -> &[AppId] {
+        self.tools.get(tool).map_or(&[], |apps| apps.as_slice())
+    }
+    pub fn tool_for(&self, app_id: &AppId) -> Option<&str> {
+        self.tools
+            .iter()
+            .find(|(_, apps)| apps.contains(app_id))
+            .map(|(tool, _)| tool.as_str())
     }
 }
 
+const MAPPING_PATH: [&str; 5] = [
+    "InstallConfigStore",
+    "Software",
+    "Valve",
+    "Steam",
+    "CompatToolMapping",
+];
+/// Priority Steam gives tools selected in an app's properties
+const USER_PRIORITY: &str = "250";
+
 fn parse_tool_name(tool_name: &str, app_id: &AppId, map: &mut CompatToolMapping) {
-    if app_id != &DEFAULT_PROTON_APP_ID {
+    if app_id == &DEFAULT_PROTON_APP_ID {
+        map.default_tool = Some(tool_name.to_string());
+    } else {
         map.entry(tool_name.to_string()).or_default().push(*app_id);
     }
 }
 
-pub fn parse_compat_tool_mapping(config_lines: impl Iterator<Item = String>) -> CompatToolMapping {
+pub fn parse_compat_tool_mapping(config: &str) -> Result<CompatToolMapping> {
     let parsers = HashMap::from([("name", parse_tool_name as KeyParser<CompatToolMapping>)]);
+    let root = vdf::parse(config)?;
 
-    parse_vdf_keys("CompatToolMapping", config_lines, &parsers, None)
+    Ok(parse_vdf_keys("CompatToolMapping", &root, &parsers, None))
+}
+
+/// Maps an app to a tool in config.vdf
+/// ```vdf
+/// "CompatToolMapping"
+/// {
+///     "[app_id]"
+///     {
+///         "name"      "[tool]"
+///         "config"    ""
+///         "priority"  "250"
+///     }
+/// }
+/// ```
+pub fn set_compat_tool(config: &mut Document, app_id: &AppId, tool: &str) -> Result<()> {
+    let id = app_id.to_string();
+    let path = |key| [&MAPPING_PATH[..], &[id.as_str(), key]].concat();
+
+    config.set(&path("name"), tool)?;
+    if config.root()?.get_path(&path("config")).is_none() {
+        config.set(&path("config"), "")?;
+    }
+    config.set(&path("priority"), USER_PRIORITY)
 }
 
 #[cfg(test)]
@@ -37,13 +96,47 @@ mod tests {
     use super::*;
 
     #[test]
-    fn parse_tool_name_excludes_app_zero() {
+    fn parse_tool_name_stores_app_zero_as_default() {
         let mut map = CompatToolMapping::new();
 
         parse_tool_name("name1", &AppId(0), &mut map);
         parse_tool_name("name2", &AppId(1), &mut map);
 
-        assert_eq!(map.0.len(), 1);
-        assert!(map.0.contains_key("name2"));
+        assert_eq!(map.tools.len(), 1);
+        assert!(map.contains_tool("name2"));
+        assert_eq!(map.default_tool.as_deref(), Some("name1"));
+        assert!(map.uses_tool("name1"));
+        assert!(!map.contains_tool("name1"));
+    }
+
+    const CONFIG: &str = "\"InstallConfigStore\"\n{\n\t\"Software\"\n\t{\n\t\t\"Valve\"\n\t\t{\n\t\t\t\"Steam\"\n\t\t\t{\n\t\t\t\t\"CompatToolMapping\"\n\t\t\t\t{\n\t\t\t\t\t\"620\"\n\t\t\t\t\t{\n\t\t\t\t\t\t\"name\"\t\t\"proton_8\"\n\t\t\t\t\t\t\"config\"\t\t\"\"\n\t\t\t\t\t\t\"priority\"\t\t\"250\"\n\t\t\t\t\t}\n\t\t\t\t}\n\t\t\t\t\"Other\"\t\t\"1\"\n\t\t\t}\n\t\t}\n\t}\n}\n";
+
+    #[test]
+    fn set_compat_tool_replaces_existing_tool() {
+        let mut config = Document::parse(CONFIG.to_string()).unwrap();
+
+        set_compat_tool(&mut config, &AppId(620), "GE-Proton8-25").unwrap();
+
+        assert_eq!(
+            config.as_str(),
+            CONFIG.replace("\"proton_8\"", "\"GE-Proton8-25\"")
+        );
+    }
+
+    #[test]
+    fn set_compat_tool_adds_new_app() {
+        let mut config = Document::parse(CONFIG.to_string()).unwrap();
+
+        set_compat_tool(&mut config, &AppId(730), "proton_8").unwrap();
+        let mapping = parse_compat_tool_mapping(config.as_str()).unwrap();
+
+        assert_eq!(mapping.tool_for(&AppId(730)), Some("proton_8"));
+        assert_eq!(mapping.tool_for(&AppId(620)), Some("proton_8"));
+        assert!(config.as_str().contains(
+            "\t\t\t\t\t\"730\"\n\t\t\t\t\t{\n\t\t\t\t\t\t\"name\"\t\t\"proton_8\"\n\t\t\t\t\t\t\"config\"\t\t\"\"\n\t\t\t\t\t\t\"priority\"\t\t\"250\"\n\t\t\t\t\t}\n\t\t\t\t}"
+        ));
+        assert!(config
+            .as_str()
+            .ends_with("\t\t\t\t\"Other\"\t\t\"1\"\n\t\t\t}\n\t\t}\n\t}\n}\n"));
     }
 }
@@ -0,0 +1,465 @@
+use super::app_info::{read_steam_play_manifest, SteamPlayManifest};
+use super::app_manifest::parse_app_manifests;
+use super::library_folders::find_libraries;
+use super::vdf::{self, Value};
+use super::{steam_roots, AppId, Result};
+use crate::read_text_config;
+use log::warn;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::path::{Path, PathBuf};
+
+/// Internal names of Valve's compatibility tool apps, for when the Steam Play manifest
+/// can't be read
+const STEAM_TOOLS: [(u64, &str); 15] = [
+    (858280, "proton_37"),
+    (961940, "proton_316"),
+    (1054830, "proton_42"),
+    (1113280, "proton_411"),
+    (1245040, "proton_5"),
+    (1420170, "proton_513"),
+    (1580130, "proton_63"),
+    (1887720, "proton_7"),
+    (2348590, "proton_8"),
+    (2805730, "proton_9"),
+    (1493710, "proton_experimental"),
+    (2180100, "proton_hotfix"),
+    (1070560, "steamlinuxruntime"),
+    (1391110, "steamlinuxruntime_soldier"),
+    (1628350, "steamlinuxruntime_sniper"),
+];
+
+/// Internal names of Valve's compatibility tools and runtimes by app id, from the Steam Play
+/// manifest. Tools it doesn't list fall back to a built-in table.
+pub fn steam_tool_names(steam_play: &SteamPlayManifest) -> HashMap<AppId, String> {
+    let mut names: HashMap<AppId, String> = STEAM_TOOLS
+        .iter()
+        .map(|(id, name)| (AppId::new(*id), name.to_string()))
+        .collect();
+    names.extend(
+        steam_play
+            .compat_tools
+            .iter()
+            .filter_map(|(name, tool)| Some((tool.app_id?, name.to_string()))),
+    );
+
+    names
+}
+
+/// Directories, other than those in Steam roots, that Steam searches for custom tools
+const SYSTEM_TOOL_DIRS: [&str; 2] = [
+    "/usr/share/steam/compatibilitytools.d",
+    "/usr/local/share/steam/compatibilitytools.d",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolSource {
+    /// Installed in a `compatibilitytools.d` directory
+    Custom,
+    /// Installed and updated by Steam as an app
+    Steam,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct InstalledTool {
+    /// Name used by `CompatToolMapping`
+    pub name: String,
+    /// Name shown in the Steam client
+    pub display_name: String,
+    /// Contents of the tool's `version` file, without the leading timestamp
+    pub version: Option<String>,
+    /// Version the tool writes to the prefixes it creates or upgrades
+    pub prefix_version: Option<String>,
+    pub install_path: PathBuf,
+    pub source: ToolSource,
+}
+
+/// Tools from the same family share a prefix format. Switching between families, or to an
+/// older version, can break a prefix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolFamily {
+    /// Valve's numbered Proton releases
+    Valve,
+    /// Proton Experimental and Hotfix
+    Experimental,
+    /// GloriousEggroll's builds
+    Ge,
+    Other,
+}
+
+impl ToolFamily {
+    /// Family of a tool by its internal name
+    pub fn from_tool_name(name: &str) -> Self {
+        match name {
+            "proton_experimental" | "proton_hotfix" => ToolFamily::Experimental,
+            _ if name.starts_with("proton_") => ToolFamily::Valve,
+            _ if name.contains("GE-Proton") || name.contains("-GE-") => ToolFamily::Ge,
+            _ => ToolFamily::Other,
+        }
+    }
+
+    /// Best guess at the family of the tool that wrote a prefix version. Experimental and
+    /// Valve versions look alike, so they can't be told apart.
+    pub fn from_prefix_version(version: &str) -> Self {
+        if version.contains("GE") {
+            ToolFamily::Ge
+        } else if version.starts_with(|c: char| c.is_ascii_digit()) {
+            ToolFamily::Valve
+        } else {
+            ToolFamily::Other
+        }
+    }
+}
+
+impl Display for ToolFamily {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ToolFamily::Valve => write!(f, "Valve Proton"),
+            ToolFamily::Experimental => write!(f, "Proton Experimental"),
+            ToolFamily::Ge => write!(f, "GE-Proton"),
+            ToolFamily::Other => write!(f, "other"),
+        }
+    }
+}
+
+/// Numbers in a prefix version, in order, for comparing versions of the same family.
+/// `8.0-105` is `[8, 0, 105]` and `GE-Proton8-25` is `[8, 25]`.
+pub fn version_numbers(version: &str) -> Vec<u32> {
+    version
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|n| n.parse().ok())
+        .collect()
+}
+
+impl InstalledTool {
+    pub fn family(&self) -> ToolFamily {
+        ToolFamily::from_tool_name(&self.name)
+    }
+
+    /// Whether this is one of the Steam Linux Runtimes that Proton and native games run in.
+    /// They're never mapped to apps, but are still needed.
+    pub fn is_runtime(&self) -> bool {
+        self.source == ToolSource::Steam && self.name.starts_with("steamlinuxruntime")
+    }
+}
+
+/// A single compatibilitytool.vdf may define multiple tools. Install paths are relative
+/// to the directory containing the file.
+/// ```vdf
+/// "compatibilitytools"
+/// {
+///     "compat_tools"
+///     {
+///         "[name]"
+///         {
+///             "install_path"  "."
+///             "display_name"  "[display_name]"
+///             ...
+///         }
+///     }
+/// }
+/// ```
+fn parse_compat_tool_vdf(contents: &str, vdf_dir: &Path) -> Result<Vec<InstalledTool>> {
+    let root = vdf::parse(contents)?;
+    let tools = root
+        .find_object("compat_tools")
+        .ok_or("Missing 'compat_tools' section")?
+        .iter()
+        .filter_map(|(name, tool)| Some((name, tool.as_object()?)))
+        .map(|(name, tool)| {
+            let string = |key| tool.get(key).and_then(Value::as_str);
+            InstalledTool {
+                name: name.to_string(),
+                display_name: string("display_name").unwrap_or(name).to_string(),
+                version: None,
+                prefix_version: None,
+                install_path: vdf_dir.join(string("install_path").unwrap_or(".")),
+                source: ToolSource::Custom,
+            }
+        })
+        .collect();
+
+    Ok(tools)
+}
+
+fn custom_tool_dirs(steam_roots: &[PathBuf]) -> Vec<PathBuf> {
+    let extra_dirs = std::env::var("STEAM_EXTRA_COMPAT_TOOLS_PATHS").unwrap_or_default();
+
+    steam_roots
+        .iter()
+        .map(|root| root.join("compatibilitytools.d"))
+        .chain(SYSTEM_TOOL_DIRS.iter().map(PathBuf::from))
+        .chain(
+            extra_dirs
+                .split(':')
+                .filter(|d| !d.is_empty())
+                .map(PathBuf::from),
+        )
+        .collect()
+}
+
+fn find_custom_tools(tools_dir: &Path) -> Vec<InstalledTool> {
+    std::fs::read_dir(tools_dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path().join("compatibilitytool.vdf"))
+        .filter(|path| path.is_file())
+        .flat_map(|path| {
+            let dir = path.parent().unwrap_or(tools_dir);
+            match read_text_config(&path).and_then(|c| parse_compat_tool_vdf(&c, dir)) {
+                Ok(tools) => tools,
+                Err(e) => {
+                    warn!("Couldn't parse '{}': {}", path.display(), e);
+                    Vec::new()
+                }
+            }
+        })
+        .collect()
+}
+
+/// Valve's tools installed as apps in `library`
+fn find_steam_tools(library: &Path, names: &HashMap<AppId, String>) -> Vec<InstalledTool> {
+    parse_app_manifests(library)
+        .into_iter()
+        .filter_map(|manifest| {
+            let name = names.get(&manifest.app_id)?;
+            Some(InstalledTool {
+                name: name.to_string(),
+                display_name: manifest.name.clone(),
+                version: None,
+                prefix_version: None,
+                install_path: manifest.install_path(),
+                source: ToolSource::Steam,
+            })
+        })
+        .collect()
+}
+
+/// Proton and most custom tools include a `version` file
+/// ```text
+/// [timestamp] [version]
+/// ```
+fn parse_tool_version(contents: &str) -> Option<String> {
+    let contents = contents.trim();
+    let version = match contents.split_once(char::is_whitespace) {
+        Some((timestamp, version)) if timestamp.parse::<u64>().is_ok() => version.trim(),
+        _ => contents,
+    };
+
+    (!version.is_empty()).then(|| version.to_string())
+}
+
+/// Proton's launch script sets the version of the prefixes it manages
+/// ```python
+/// CURRENT_PREFIX_VERSION="[version]"
+/// ```
+fn parse_prefix_version(proton_script: &str) -> Option<String> {
+    proton_script
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("CURRENT_PREFIX_VERSION"))
+        .and_then(|rest| rest.trim_start().strip_prefix('='))
+        .map(|version| version.trim().trim_matches(['"', '\'']).to_string())
+        .filter(|version| !version.is_empty())
+}
+
+/// Custom tools from every `compatibilitytools.d` directory Steam searches and
+/// Valve's tools installed as apps in any library
+pub fn find_installed_tools(steam_home: &Path) -> Vec<InstalledTool> {
+    let roots = steam_roots(steam_home);
+    let custom_dirs = custom_tool_dirs(&roots);
+    let libraries = find_libraries(steam_home);
+    let steam_play = read_steam_play_manifest(steam_home).unwrap_or_default();
+    let names = steam_tool_names(&steam_play);
+    let mut result: Vec<InstalledTool> = Vec::new();
+    let tools = custom_dirs
+        .iter()
+        .flat_map(|dir| find_custom_tools(dir))
+        .chain(
+            libraries
+                .iter()
+                .flat_map(|library| find_steam_tools(library, &names)),
+        );
+
+    for mut tool in tools {
+        if let Ok(path) = tool.install_path.canonicalize() {
+            tool.install_path = path;
+        }
+        tool.version = std::fs::read_to_string(tool.install_path.join("version"))
+            .ok()
+            .and_then(|v| parse_tool_version(&v));
+        tool.prefix_version = std::fs::read_to_string(tool.install_path.join("proton"))
+            .ok()
+            .and_then(|script| parse_prefix_version(&script));
+        if !result.contains(&tool) {
+            result.push(tool);
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::steam::app_info::ValveCompatTool;
+
+    #[test]
+    fn can_parse_compat_tool_vdf() {
+        let contents = r#"
+            "compatibilitytools"
+            {
+              "compat_tools"
+              {
+                "GE-Proton8-25" // Internal name of this tool
+                {
+                  "install_path" "."
+                  "display_name" "GE-Proton8-25 Display"
+                  "from_oslist"  "windows"
+                  "to_oslist"    "linux"
+                }
+                "Other"
+                {
+                  "install_path" "other"
+                }
+              }
+            }"#;
+        let dir = Path::new("/tools/GE-Proton8-25");
+
+        let tools = parse_compat_tool_vdf(contents, dir).unwrap();
+
+        assert_eq!(
+            tools,
+            vec![
+                InstalledTool {
+                    name: "GE-Proton8-25".to_string(),
+                    display_name: "GE-Proton8-25 Display".to_string(),
+                    version: None,
+                    prefix_version: None,
+                    install_path: dir.join("."),
+                    source: ToolSource::Custom,
+                },
+                InstalledTool {
+                    name: "Other".to_string(),
+                    display_name: "Other".to_string(),
+                    version: None,
+                    prefix_version: None,
+                    install_path: dir.join("other"),
+                    source: ToolSource::Custom,
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn tool_version_excludes_timestamp() {
+        assert_eq!(
+            parse_tool_version("1700000000 proton-8.0-5\n"),
+            Some("proton-8.0-5".to_string())
+        );
+        assert_eq!(
+            parse_tool_version("GE-Proton8-25"),
+            Some("GE-Proton8-25".to_string())
+        );
+        assert_eq!(parse_tool_version(" \n"), None);
+    }
+
+    #[test]
+    fn errors_if_compat_tools_missing() {
+        let contents = r#""compatibilitytools" { }"#;
+
+        assert!(parse_compat_tool_vdf(contents, Path::new("/")).is_err());
+    }
+
+    #[test]
+    fn can_parse_prefix_version() {
+        let script = "#!/usr/bin/env python3\n\n#script version\nCURRENT_PREFIX_VERSION=\"8.0-105\"\n\nPFX=\"Proton: \"\n";
+
+        assert_eq!(parse_prefix_version(script), Some("8.0-105".to_string()));
+        assert_eq!(parse_prefix_version("PFX=\"Proton: \""), None);
+    }
+
+    #[test]
+    fn tool_families() {
+        assert_eq!(ToolFamily::from_tool_name("proton_8"), ToolFamily::Valve);
+        assert_eq!(
+            ToolFamily::from_tool_name("proton_experimental"),
+            ToolFamily::Experimental
+        );
+        assert_eq!(ToolFamily::from_tool_name("GE-Proton8-25"), ToolFamily::Ge);
+        assert_eq!(
+            ToolFamily::from_tool_name("Proton-6.5-GE-2"),
+            ToolFamily::Ge
+        );
+        assert_eq!(ToolFamily::from_tool_name("luxtorpeda"), ToolFamily::Other);
+        assert_eq!(
+            ToolFamily::from_prefix_version("GE-Proton8-25"),
+            ToolFamily::Ge
+        );
+        assert_eq!(
+            ToolFamily::from_prefix_version("8.0-105"),
+            ToolFamily::Valve
+        );
+    }
+
+    #[test]
+    fn recognizes_steam_runtimes() {
+        let tool = |name: &str, source| InstalledTool {
+            name: name.to_string(),
+            display_name: name.to_string(),
+            version: None,
+            prefix_version: None,
+            install_path: PathBuf::new(),
+            source,
+        };
+
+        assert!(tool("steamlinuxruntime_sniper", ToolSource::Steam).is_runtime());
+        assert!(tool("steamlinuxruntime", ToolSource::Steam).is_runtime());
+        assert!(!tool("proton_8", ToolSource::Steam).is_runtime());
+        assert!(!tool("steamlinuxruntime_custom", ToolSource::Custom).is_runtime());
+    }
+
+    #[test]
+    fn steam_tools_come_from_the_steam_play_manifest() {
+        let tool = |app_id| ValveCompatTool {
+            app_id,
+            display_name: None,
+            aliases: Vec::new(),
+            from_os_list: None,
+            to_os_list: None,
+        };
+        let steam_play = SteamPlayManifest {
+            app_mappings: HashMap::new(),
+            compat_tools: HashMap::from([
+                ("proton_10".to_string(), tool(Some(AppId::new(3658110)))),
+                (
+                    "steamlinuxruntime_4".to_string(),
+                    tool(Some(AppId::new(3810310))),
+                ),
+                ("proton_broken".to_string(), tool(None)),
+            ]),
+        };
+
+        let names = steam_tool_names(&steam_play);
+
+        assert_eq!(names[&AppId::new(3658110)], "proton_10");
+        assert_eq!(names[&AppId::new(3810310)], "steamlinuxruntime_4");
+        // Not in this manifest, so from the built-in table
+        assert_eq!(names[&AppId::new(2348590)], "proton_8");
+        assert_eq!(
+            steam_tool_names(&SteamPlayManifest::default()).len(),
+            STEAM_TOOLS.len()
+        );
+    }
+
+    #[test]
+    fn version_numbers_compare_in_order() {
+        assert_eq!(version_numbers("8.0-105"), [8, 0, 105]);
+        assert_eq!(version_numbers("GE-Proton8-25"), [8, 25]);
+        assert!(version_numbers("7.0-6") < version_numbers("8.0-105"));
+        assert!(version_numbers("GE-Proton8-3") < version_numbers("GE-Proton8-25"));
+    }
+}
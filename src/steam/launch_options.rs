@@ -0,0 +1,762 @@
+//! Launch options as Steam runs them. `%command%` is replaced with the game's command line and
+//! the result is run by a shell, so anything before it is environment variables and wrappers.
+//! Without `%command%`, everything is appended to the game's arguments.
+//! ```text
+//! [NAME=value ...] [wrapper [args] ...] %command% [game args]
+//! ```
+use super::Result;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::fmt::{self, Display, Formatter};
+use std::ops::Range;
+use std::str::FromStr;
+
+pub const COMMAND: &str = "%command%";
+
+/// Wrappers that may follow another wrapper. Any other word is taken as an argument of the
+/// wrapper before it, unless that wrapper's arguments end with `--`.
+const KNOWN_WRAPPERS: &[&str] = &[
+    "env",
+    "firejail",
+    "game-performance",
+    "gamemoderun",
+    "gamescope",
+    "mangohud",
+    "nice",
+    "obs-gamecapture",
+    "optirun",
+    "prime-run",
+    "primusrun",
+    "pvkrun",
+    "strangle",
+    "taskset",
+];
+
+/// Variables Proton reads. Other `PROTON_` variables are likely typos.
+/// https://github.com/ValveSoftware/Proton#runtime-config-options
+const PROTON_VARS: &[&str] = &[
+    "PROTON_CRASH_REPORT_DIR",
+    "PROTON_DEBUG_DIR",
+    "PROTON_DISABLE_LSTEAMCLIENT",
+    "PROTON_DUMP_DEBUG_COMMANDS",
+    "PROTON_ENABLE_HDR",
+    "PROTON_ENABLE_NGX_UPDATER",
+    "PROTON_ENABLE_NVAPI",
+    "PROTON_ENABLE_WAYLAND",
+    "PROTON_FORCE_LARGE_ADDRESS_AWARE",
+    "PROTON_HEAP_DELAY_FREE",
+    "PROTON_HIDE_NVIDIA_GPU",
+    "PROTON_LOCAL_SHADER_CACHE",
+    "PROTON_LOG",
+    "PROTON_LOG_DIR",
+    "PROTON_NO_D3D11",
+    "PROTON_NO_D3D12",
+    "PROTON_NO_ESYNC",
+    "PROTON_NO_FSYNC",
+    "PROTON_NO_NTSYNC",
+    "PROTON_NO_STEAMINPUT",
+    "PROTON_NO_WRITE_WATCH",
+    "PROTON_NO_XIM",
+    "PROTON_OLD_GL_STRING",
+    "PROTON_PREFER_SDL",
+    "PROTON_SET_GAME_DRIVE",
+    "PROTON_USE_NTSYNC",
+    "PROTON_USE_WINED3D",
+    "PROTON_USE_WOW64",
+    "PROTON_USE_XALIA",
+    "PROTON_WAIT_ATTACH",
+    // GE-Proton
+    "PROTON_DLSS_UPGRADE",
+    "PROTON_ENABLE_AMD_AGS",
+    "PROTON_FSR4_UPGRADE",
+    "PROTON_NO_WM_DECORATION",
+    "PROTON_USE_SDL",
+    "PROTON_XESS_UPGRADE",
+];
+
+/// Variables older versions of Proton read that no longer do anything
+const OBSOLETE_PROTON_VARS: &[&str] = &[
+    "PROTON_NO_D3D10",
+    "PROTON_NO_D9VK",
+    "PROTON_NO_GLSL",
+    "PROTON_USE_D9VK",
+    "PROTON_USE_SECCOMP",
+];
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EnvVar {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Wrapper {
+    pub command: String,
+    pub args: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ParsedLaunchOptions {
+    /// Leading `NAME=value` assignments
+    pub env: Vec<EnvVar>,
+    /// Commands before `%command%`, in the order they wrap each other
+    pub wrappers: Vec<Wrapper>,
+    /// Whether `%command%` is present
+    pub command: bool,
+    /// Arguments after `%command%`, or everything after the environment variables without it
+    pub args: Vec<String>,
+}
+
+/// A word and where it is in the launch options, including its quotes
+struct Word {
+    text: String,
+    span: Range<usize>,
+}
+
+fn split_spanned(options: &str) -> Result<Vec<Word>> {
+    let mut words = Vec::new();
+    let mut word: Option<(String, usize)> = None;
+    let mut chars = options.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        if c.is_whitespace() {
+            if let Some((text, start)) = word.take() {
+                words.push(Word {
+                    text,
+                    span: start..i,
+                });
+            }
+            continue;
+        }
+
+        let (word, _) = word.get_or_insert_with(|| (String::new(), i));
+        let mut next = || chars.next().map(|(_, c)| c);
+        match c {
+            '\'' => loop {
+                match next() {
+                    Some('\'') => break,
+                    Some(c) => word.push(c),
+                    None => return Err("Unclosed ' quote".into()),
+                }
+            },
+            '"' => loop {
+                match next() {
+                    Some('"') => break,
+                    Some('\\') => match next() {
+                        Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                        Some(c) => {
+                            word.push('\\');
+                            word.push(c);
+                        }
+                        None => return Err("Unclosed \" quote".into()),
+                    },
+                    Some(c) => word.push(c),
+                    None => return Err("Unclosed \" quote".into()),
+                }
+            },
+            '\\' => word.push(next().unwrap_or('\\')),
+            c => word.push(c),
+        }
+    }
+    if let Some((text, start)) = word {
+        words.push(Word {
+            text,
+            span: start..options.len(),
+        });
+    }
+
+    Ok(words)
+}
+
+/// Splits `options` into words like a shell would. Single quotes are literal, double quotes
+/// allow backslash escapes and a backslash outside of quotes escapes the next character.
+pub fn split_words(options: &str) -> Result<Vec<String>> {
+    Ok(split_spanned(options)?
+        .into_iter()
+        .map(|word| word.text)
+        .collect())
+}
+
+/// Quotes a word so `split_words` reads it back unchanged
+pub fn quote(word: &str) -> Cow<'_, str> {
+    let plain = !word.is_empty()
+        && !word.contains(|c: char| c.is_whitespace() || "\"'\\$`;&|<>()*?#".contains(c));
+    if plain {
+        return Cow::Borrowed(word);
+    }
+
+    let mut quoted = String::with_capacity(word.len() + 2);
+    quoted.push('"');
+    for c in word.chars() {
+        if matches!(c, '"' | '\\' | '$' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+
+    Cow::Owned(quoted)
+}
+
+/// `NAME=value` where the name is a valid shell variable name
+pub fn parse_assignment(word: &str) -> Option<EnvVar> {
+    let (name, value) = word.split_once('=')?;
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    valid.then(|| EnvVar {
+        name: name.to_string(),
+        value: value.to_string(),
+    })
+}
+
+fn is_known_wrapper(word: &str) -> bool {
+    let name = word.rsplit('/').next().unwrap_or(word);
+    KNOWN_WRAPPERS.contains(&name)
+}
+
+fn parse_wrappers(words: Vec<String>) -> Vec<Wrapper> {
+    let mut wrappers: Vec<Wrapper> = Vec::new();
+    for word in words {
+        match wrappers.last_mut() {
+            Some(wrapper)
+                if !is_known_wrapper(&word) && wrapper.args.last().is_none_or(|a| a != "--") =>
+            {
+                wrapper.args.push(word)
+            }
+            _ => wrappers.push(Wrapper {
+                command: word,
+                args: Vec::new(),
+            }),
+        }
+    }
+
+    wrappers
+}
+
+pub fn parse_launch_options(options: &str) -> Result<ParsedLaunchOptions> {
+    let mut words = split_words(options)?.into_iter().peekable();
+    let mut parsed = ParsedLaunchOptions::default();
+
+    while let Some(var) = words.peek().and_then(|word| parse_assignment(word)) {
+        parsed.env.push(var);
+        words.next();
+    }
+
+    let rest: Vec<String> = words.collect();
+    match rest.iter().position(|word| word == COMMAND) {
+        Some(i) => {
+            let mut wrappers = rest;
+            parsed.args = wrappers.split_off(i + 1);
+            wrappers.pop();
+            parsed.wrappers = parse_wrappers(wrappers);
+            parsed.command = true;
+        }
+        None => parsed.args = rest,
+    }
+
+    Ok(parsed)
+}
+
+impl Display for ParsedLaunchOptions {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let env = self
+            .env
+            .iter()
+            .map(|var| Cow::Owned(format!("{}={}", var.name, quote(&var.value))));
+        let wrappers = self.wrappers.iter().flat_map(|wrapper| {
+            std::iter::once(&wrapper.command)
+                .chain(&wrapper.args)
+                .map(|word| quote(word))
+        });
+        let command = self.command.then_some(Cow::Borrowed(COMMAND));
+        let args = self.args.iter().map(|arg| quote(arg));
+        let words: Vec<Cow<str>> = env.chain(wrappers).chain(command).chain(args).collect();
+
+        write!(f, "{}", words.join(" "))
+    }
+}
+
+impl FromStr for EnvVar {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        parse_assignment(s).ok_or_else(|| format!("'{}' isn't a NAME=VALUE assignment", s))
+    }
+}
+
+/// Leading words that are environment variable assignments, with their variable
+fn env_words(words: &[Word]) -> Vec<(&Word, EnvVar)> {
+    words
+        .iter()
+        .map_while(|word| Some((word, parse_assignment(&word.text)?)))
+        .collect()
+}
+
+/// Removes words from `options` along with the whitespace after them, or before them if
+/// they're last
+fn remove_words(options: &mut String, mut spans: Vec<Range<usize>>) {
+    spans.sort_by_key(|span| span.start);
+    for span in spans.into_iter().rev() {
+        let rest = &options[span.end..];
+        let end = span.end + (rest.len() - rest.trim_start().len());
+        let start = if end == options.len() {
+            options[..span.start].trim_end().len()
+        } else {
+            span.start
+        };
+        options.replace_range(start..end, "");
+    }
+}
+
+/// Adds `%command%` after the leading environment variables if it's missing
+fn add_command(options: &str) -> Result<String> {
+    let words = split_spanned(options)?;
+    if words.iter().any(|word| word.text == COMMAND) {
+        return Ok(options.to_string());
+    }
+
+    let mut result = options.to_string();
+    match words.get(env_words(&words).len()) {
+        Some(next) => result.insert_str(next.span.start, &format!("{} ", COMMAND)),
+        None => result = format!("{} {}", options.trim_end(), COMMAND),
+    }
+
+    Ok(result)
+}
+
+/// Sets an environment variable, replacing the first assignment if it's already set and
+/// removing any others. The rest of the options are left as they are, except that
+/// `%command%` is added if it's missing, since variables have no effect without it.
+pub fn set_env(options: &str, var: &EnvVar) -> Result<String> {
+    let words = split_spanned(options)?;
+    let env = env_words(&words);
+    let assignment = format!("{}={}", var.name, quote(&var.value));
+    let mut result = options.to_string();
+
+    let mut existing = env
+        .iter()
+        .filter(|(_, v)| v.name == var.name)
+        .map(|(w, _)| w);
+    if let Some(first) = existing.next() {
+        let others = existing.map(|word| word.span.clone()).collect();
+        remove_words(&mut result, others);
+        result.replace_range(first.span.clone(), &assignment);
+    } else if let Some(next) = words.get(env.len()) {
+        result.insert_str(next.span.start, &format!("{} ", assignment));
+    } else {
+        let prefix = options.trim_end();
+        let separator = if prefix.is_empty() { "" } else { " " };
+        result = format!("{}{}{}", prefix, separator, assignment);
+    }
+
+    add_command(&result)
+}
+
+/// Renames an environment variable, keeping its value. If `to` is already set, its value wins
+/// and `from` is removed instead. `None` if `from` isn't set.
+pub fn rename_env(options: &str, from: &str, to: &str) -> Result<Option<String>> {
+    if parse_assignment(&format!("{}=", to)).is_none() {
+        return Err(format!("'{}' isn't a valid variable name", to).into());
+    }
+    let words = split_spanned(options)?;
+    let env = env_words(&words);
+    if env.iter().any(|(_, var)| var.name == to) {
+        return remove_env(options, from);
+    }
+
+    let mut renamed: Vec<(Range<usize>, String)> = env
+        .iter()
+        .filter(|(_, var)| var.name == from)
+        .map(|(word, var)| (word.span.clone(), format!("{}={}", to, quote(&var.value))))
+        .collect();
+    if renamed.is_empty() {
+        return Ok(None);
+    }
+
+    let mut result = options.to_string();
+    renamed.sort_by_key(|(span, _)| span.start);
+    for (span, assignment) in renamed.into_iter().rev() {
+        result.replace_range(span, &assignment);
+    }
+
+    Ok(Some(result))
+}
+
+/// Removes every assignment of an environment variable. `None` if it isn't set.
+pub fn remove_env(options: &str, name: &str) -> Result<Option<String>> {
+    let words = split_spanned(options)?;
+    let spans: Vec<Range<usize>> = env_words(&words)
+        .into_iter()
+        .filter(|(_, var)| var.name == name)
+        .map(|(word, _)| word.span.clone())
+        .collect();
+    if spans.is_empty() {
+        return Ok(None);
+    }
+
+    let mut result = options.to_string();
+    remove_words(&mut result, spans);
+
+    Ok(Some(result))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    /// The options don't do what they were meant to
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A likely mistake in launch options
+#[derive(Clone, Debug, PartialEq)]
+pub enum Lint {
+    /// Quotes that are never closed
+    UnbalancedQuotes(String),
+    /// Environment variables without `%command%` are passed to the game as arguments
+    MissingCommand,
+    /// Looks like an environment variable, but is passed to the game as an argument
+    EnvAfterCommand(String),
+    ObsoleteVar(String),
+    UnknownVar(String),
+    /// Set more than once. The last one wins.
+    DuplicateVar(String),
+    /// The shell removes unquoted backslashes
+    WindowsPath(String),
+}
+
+impl Lint {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Lint::UnbalancedQuotes(_) | Lint::MissingCommand | Lint::EnvAfterCommand(_) => {
+                Severity::Error
+            }
+            _ => Severity::Warning,
+        }
+    }
+
+    /// Name of the check that found it
+    pub fn check(&self) -> &'static str {
+        match self {
+            Lint::UnbalancedQuotes(_) => "unbalanced_quotes",
+            Lint::MissingCommand => "missing_command",
+            Lint::EnvAfterCommand(_) => "env_after_command",
+            Lint::ObsoleteVar(_) => "obsolete_var",
+            Lint::UnknownVar(_) => "unknown_var",
+            Lint::DuplicateVar(_) => "duplicate_var",
+            Lint::WindowsPath(_) => "windows_path",
+        }
+    }
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Lint::UnbalancedQuotes(e) => write!(f, "{}", e),
+            Lint::MissingCommand => write!(
+                f,
+                "Environment variables need {} after them, otherwise they're passed to the game",
+                COMMAND
+            ),
+            Lint::EnvAfterCommand(name) => write!(
+                f,
+                "{} is after {}, so it's passed to the game instead of being set",
+                name, COMMAND
+            ),
+            Lint::ObsoleteVar(name) => {
+                write!(f, "{} is no longer supported by Proton", name)
+            }
+            Lint::UnknownVar(name) => write!(f, "{} isn't a known Proton variable", name),
+            Lint::DuplicateVar(name) => {
+                write!(f, "{} is set more than once. The last one is used.", name)
+            }
+            Lint::WindowsPath(path) => write!(
+                f,
+                "Windows path '{}' loses its backslashes unless it's quoted",
+                path
+            ),
+        }
+    }
+}
+
+/// Words in `options` that start with an unquoted drive letter, like `C:\Games`. Quoted paths
+/// keep their backslashes. Nothing is found if the quotes are unbalanced.
+fn find_windows_paths(options: &str) -> Vec<String> {
+    split_spanned(options)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|word| &options[word.span])
+        .filter(|raw| {
+            let mut chars = raw.chars();
+            chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+                && chars.next() == Some(':')
+                && chars.next() == Some('\\')
+        })
+        .map(|raw| raw.to_string())
+        .collect()
+}
+
+/// Checks launch options for common mistakes. Windows paths are only checked for shortcuts,
+/// whose options are more likely to point at files.
+pub fn lint_launch_options(options: &str, shortcut: bool) -> Vec<Lint> {
+    let mut lints = Vec::new();
+    match parse_launch_options(options) {
+        Err(e) => lints.push(Lint::UnbalancedQuotes(e.to_string())),
+        Ok(parsed) => {
+            if !parsed.env.is_empty() && !parsed.command {
+                lints.push(Lint::MissingCommand);
+            }
+            if parsed.command {
+                let after = parsed
+                    .args
+                    .iter()
+                    .filter_map(|arg| parse_assignment(arg))
+                    .filter(|var| !var.name.contains(|c: char| c.is_ascii_lowercase()));
+                lints.extend(after.map(|var| Lint::EnvAfterCommand(var.name)));
+            }
+
+            let mut seen: Vec<&str> = Vec::new();
+            for var in &parsed.env {
+                let name = var.name.as_str();
+                if seen.contains(&name) {
+                    if !lints.contains(&Lint::DuplicateVar(name.to_string())) {
+                        lints.push(Lint::DuplicateVar(name.to_string()));
+                    }
+                    continue;
+                }
+                seen.push(name);
+
+                if OBSOLETE_PROTON_VARS.contains(&name) {
+                    lints.push(Lint::ObsoleteVar(name.to_string()));
+                } else if name.starts_with("PROTON_") && !PROTON_VARS.contains(&name) {
+                    lints.push(Lint::UnknownVar(name.to_string()));
+                }
+            }
+        }
+    }
+
+    if shortcut {
+        lints.extend(
+            find_windows_paths(options)
+                .into_iter()
+                .map(Lint::WindowsPath),
+        );
+    }
+
+    lints
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn var(name: &str, value: &str) -> EnvVar {
+        EnvVar {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn splits_words_like_a_shell() {
+        assert_eq!(
+            split_words(r#"A="b c" 'd "e"' f\ g "h\"i\j""#).unwrap(),
+            ["A=b c", "d \"e\"", "f g", "h\"i\\j"]
+        );
+        assert_eq!(split_words(r#""" x"#).unwrap(), ["", "x"]);
+        assert!(split_words(r#"A="b %command%"#).is_err());
+        assert!(split_words("A='b %command%").is_err());
+    }
+
+    #[test]
+    fn parses_env_wrappers_and_args() {
+        let parsed = parse_launch_options(
+            "DXVK_ASYNC=1 PROTON_LOG=\"1\" gamescope -w 1920 -f -- mangohud gamemoderun \
+             %command% -novid +exec \"my config.cfg\"",
+        )
+        .unwrap();
+
+        assert_eq!(
+            parsed,
+            ParsedLaunchOptions {
+                env: vec![var("DXVK_ASYNC", "1"), var("PROTON_LOG", "1")],
+                wrappers: vec![
+                    Wrapper {
+                        command: "gamescope".to_string(),
+                        args: vec!["-w", "1920", "-f", "--"]
+                            .into_iter()
+                            .map(String::from)
+                            .collect(),
+                    },
+                    Wrapper {
+                        command: "mangohud".to_string(),
+                        args: vec![],
+                    },
+                    Wrapper {
+                        command: "gamemoderun".to_string(),
+                        args: vec![],
+                    },
+                ],
+                command: true,
+                args: vec!["-novid", "+exec", "my config.cfg"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+            }
+        );
+    }
+
+    #[test]
+    fn everything_is_an_argument_without_command() {
+        let parsed = parse_launch_options("PROTON_LOG=1 -novid -high").unwrap();
+
+        assert_eq!(parsed.env, [var("PROTON_LOG", "1")]);
+        assert!(parsed.wrappers.is_empty());
+        assert!(!parsed.command);
+        assert_eq!(parsed.args, ["-novid", "-high"]);
+    }
+
+    #[test]
+    fn unknown_words_are_wrapper_args() {
+        let parsed = parse_launch_options("strangle 60 /usr/bin/gamemoderun %command%").unwrap();
+        let commands: Vec<&str> = parsed.wrappers.iter().map(|w| w.command.as_str()).collect();
+
+        assert_eq!(commands, ["strangle", "/usr/bin/gamemoderun"]);
+        assert_eq!(parsed.wrappers[0].args, ["60"]);
+    }
+
+    #[test]
+    fn displays_quoted_options() {
+        let options = r#"WINEDLLOVERRIDES="d3d9=n,b" A="x y" mangohud %command% -name "a\"b""#;
+
+        assert_eq!(
+            parse_launch_options(options).unwrap().to_string(),
+            r#"WINEDLLOVERRIDES=d3d9=n,b A="x y" mangohud %command% -name "a\"b""#
+        );
+    }
+
+    #[test]
+    fn lints_env_placement() {
+        assert_eq!(
+            lint_launch_options("PROTON_LOG=1 -novid", false),
+            [Lint::MissingCommand]
+        );
+        assert_eq!(
+            lint_launch_options("%command% DXVK_HUD=fps -novid lang=en", false),
+            [Lint::EnvAfterCommand("DXVK_HUD".to_string())]
+        );
+        assert!(lint_launch_options("PROTON_LOG=1 gamemoderun %command%", false).is_empty());
+        assert!(lint_launch_options("-novid", false).is_empty());
+    }
+
+    #[test]
+    fn lints_proton_vars() {
+        assert_eq!(
+            lint_launch_options(
+                "PROTON_NO_D3D10=1 PROTON_USE_WINE3D=1 DXVK_ASYNC=1 DXVK_ASYNC=0 %command%",
+                false
+            ),
+            [
+                Lint::ObsoleteVar("PROTON_NO_D3D10".to_string()),
+                Lint::UnknownVar("PROTON_USE_WINE3D".to_string()),
+                Lint::DuplicateVar("DXVK_ASYNC".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn lints_quotes_and_windows_paths() {
+        let lints = lint_launch_options(r#"%command% -config "C:\Games\a.cfg"#, true);
+
+        assert_eq!(lints.len(), 1);
+        assert_eq!(lints[0].severity(), Severity::Error);
+        assert_eq!(lints[0].check(), "unbalanced_quotes");
+        assert_eq!(
+            lint_launch_options(r"%command% -config C:\Games\a.cfg", true),
+            [Lint::WindowsPath(r"C:\Games\a.cfg".to_string())]
+        );
+        assert!(lint_launch_options(r"%command% C:\Games", false).is_empty());
+    }
+
+    #[test]
+    fn allows_quoted_windows_paths() {
+        let options = r#"%command% -config "C:\Games\a.cfg" 'D:\x.ini' "C:\Program Files\x.exe""#;
+
+        assert!(lint_launch_options(options, true).is_empty());
+    }
+
+    #[test]
+    fn sets_env_without_touching_the_rest() {
+        let options = r#"A=1  gamemoderun "%command%" -name "x y""#;
+
+        assert_eq!(
+            set_env(options, &var("B", "2 3")).unwrap(),
+            r#"A=1  B="2 3" gamemoderun "%command%" -name "x y""#
+        );
+        assert_eq!(
+            set_env(options, &var("A", "0")).unwrap(),
+            r#"A=0  gamemoderun "%command%" -name "x y""#
+        );
+        assert_eq!(
+            set_env("A=1 B=2 A=3 %command%", &var("A", "4")).unwrap(),
+            "A=4 B=2 %command%"
+        );
+    }
+
+    #[test]
+    fn set_env_adds_missing_command() {
+        assert_eq!(set_env("", &var("A", "1")).unwrap(), "A=1 %command%");
+        assert_eq!(
+            set_env("-novid", &var("A", "1")).unwrap(),
+            "A=1 %command% -novid"
+        );
+        assert_eq!(
+            set_env("A=1 -novid", &var("A", "2")).unwrap(),
+            "A=2 %command% -novid"
+        );
+        assert_eq!(set_env("A=1", &var("A", "2")).unwrap(), "A=2 %command%");
+    }
+
+    #[test]
+    fn renames_env() {
+        assert_eq!(
+            rename_env(r#"OLD="a b"  %command% OLD=1"#, "OLD", "NEW")
+                .unwrap()
+                .as_deref(),
+            Some(r#"NEW="a b"  %command% OLD=1"#)
+        );
+        assert_eq!(
+            rename_env("OLD=1 NEW=2 %command%", "OLD", "NEW")
+                .unwrap()
+                .as_deref(),
+            Some("NEW=2 %command%")
+        );
+        assert_eq!(rename_env("A=1 %command%", "OLD", "NEW").unwrap(), None);
+        assert!(rename_env("OLD=1 %command%", "OLD", "1NEW").is_err());
+    }
+
+    #[test]
+    fn removes_env() {
+        assert_eq!(
+            remove_env("A=1 B=2 A=3 %command% A=4", "A")
+                .unwrap()
+                .as_deref(),
+            Some("B=2 %command% A=4")
+        );
+        assert_eq!(remove_env("B=2 A=1", "A").unwrap().as_deref(), Some("B=2"));
+        assert_eq!(remove_env("B=2 %command%", "A").unwrap(), None);
+    }
+}
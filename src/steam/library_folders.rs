@@ -0,0 +1,160 @@
+use super::app_manifest::{parse_app_manifests, AppManifest};
+use super::vdf::{self, Value};
+use super::{steam_roots, AppId, Result};
+use crate::read_text_config;
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Supports both the current format and the older one where each library is only a path
+/// ```vdf
+/// "libraryfolders"
+/// {
+///     "0"
+///     {
+///         "path"  "[library_path]"
+///         "apps"  { "[app_id]" "[size]" ... }
+///         ...
+///     }
+///     "1"     "[library_path]"
+/// }
+/// ```
+pub fn parse_library_folders(contents: &str) -> Result<Vec<PathBuf>> {
+    let root = vdf::parse(contents)?;
+    let folders = root
+        .get_object("libraryfolders")
+        .ok_or("Missing 'libraryfolders' section")?
+        .iter()
+        .filter(|(key, _)| key.parse::<u32>().is_ok())
+        .filter_map(|(_, folder)| match folder {
+            Value::String(path) => Some(path.as_str()),
+            Value::Object(folder) => folder.get("path").and_then(Value::as_str),
+            _ => None,
+        })
+        .map(PathBuf::from)
+        .collect();
+
+    Ok(folders)
+}
+
+/// Library folders listed in a Steam root's libraryfolders.vdf
+fn library_folders(root: &Path) -> Result<Vec<PathBuf>> {
+    let config_path = ["steamapps", "config"]
+        .iter()
+        .map(|dir| root.join(dir).join("libraryfolders.vdf"))
+        .find(|path| path.is_file());
+    match config_path {
+        Some(path) => {
+            debug!("Parsing {}", path.display());
+            read_text_config(&path)
+                .and_then(|c| parse_library_folders(&c))
+                .map_err(|e| format!("Couldn't parse '{}': {}", path.display(), e).into())
+        }
+        None => Ok(Vec::new()),
+    }
+}
+
+fn collect_libraries(
+    steam_home: &Path,
+    folders: impl Fn(&Path) -> Result<Vec<PathBuf>>,
+) -> Result<Vec<PathBuf>> {
+    let mut libraries: Vec<PathBuf> = Vec::new();
+    for root in steam_roots(steam_home) {
+        let folders = folders(&root)?;
+        for library in std::iter::once(root).chain(folders) {
+            let library = library.canonicalize().unwrap_or(library);
+            if !libraries.contains(&library) {
+                libraries.push(library);
+            }
+        }
+    }
+
+    Ok(libraries)
+}
+
+/// Every Steam root and the library folders they list
+pub fn find_libraries(steam_home: &Path) -> Vec<PathBuf> {
+    collect_libraries(steam_home, |root| {
+        Ok(library_folders(root).unwrap_or_else(|e| {
+            warn!("{}", e);
+            Vec::new()
+        }))
+    })
+    .unwrap_or_default()
+}
+
+/// Like [`find_libraries`], but errors if a libraryfolders.vdf can't be parsed instead of
+/// leaving its libraries out
+pub fn try_find_libraries(steam_home: &Path) -> Result<Vec<PathBuf>> {
+    collect_libraries(steam_home, library_folders)
+}
+
+/// App manifests from every library. If an app has manifests in multiple libraries,
+/// the fully installed one is preferred.
+pub fn find_installed_apps(steam_home: &Path) -> HashMap<AppId, AppManifest> {
+    let mut result: HashMap<AppId, AppManifest> = HashMap::new();
+    for manifest in find_libraries(steam_home)
+        .iter()
+        .flat_map(|library| parse_app_manifests(library))
+    {
+        match result.get(&manifest.app_id) {
+            Some(existing) if existing.state_flags.is_fully_installed() => {}
+            _ => {
+                result.insert(manifest.app_id, manifest);
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_library_folders() {
+        let contents = r#"
+            "libraryfolders"
+            {
+                "0"
+                {
+                    "path"		"/home/user/.local/share/Steam"
+                    "label"		""
+                    "apps"
+                    {
+                        "620"		"12345"
+                    }
+                }
+                "1"
+                {
+                    "path"		"/mnt/games/SteamLibrary"
+                }
+            }"#;
+
+        let folders = parse_library_folders(contents).unwrap();
+
+        assert_eq!(
+            folders,
+            vec![
+                PathBuf::from("/home/user/.local/share/Steam"),
+                PathBuf::from("/mnt/games/SteamLibrary")
+            ]
+        );
+    }
+
+    #[test]
+    fn can_parse_legacy_library_folders() {
+        let contents = r#"
+            "LibraryFolders"
+            {
+                "TimeNextStatsReport"		"1637000000"
+                "ContentStatsID"		"-1234"
+                "1"		"/mnt/games/SteamLibrary"
+            }"#;
+
+        let folders = parse_library_folders(contents).unwrap();
+
+        assert_eq!(folders, vec![PathBuf::from("/mnt/games/SteamLibrary")]);
+    }
+}
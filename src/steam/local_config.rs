@@ -1,10 +1,15 @@
+use super::vdf_edit::Document;
 use super::{
-    get_userdata_file, parse_vdf_keys, AppId, KeyParser, Result, SteamId64, DEFAULT_PROTON_APP_ID,
+    get_userdata_file, parse_vdf_keys, root_dir, vdf, AppId, KeyParser, Result, SteamId64, UserId,
+    DEFAULT_PROTON_APP_ID,
 };
-use crate::open_text_config;
+use crate::read_text_config;
 use log::warn;
 use std::collections::{BTreeMap, HashMap};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+const CONFIG_PATH: &str = "config/localconfig.vdf";
+const APPS_PATH: [&str; 5] = ["UserLocalConfigStore", "Software", "Valve", "Steam", "apps"];
 
 pub struct LaunchOptions {
     pub app_id: AppId,
@@ -28,19 +33,101 @@ pub fn parse_launch_options_mapping(
         parse_launch_options as KeyParser<Vec<LaunchOptions>>,
     )]);
 
-    get_userdata_file(steam_home, "config/localconfig.vdf")?
+    get_userdata_file(steam_home, CONFIG_PATH)?
         .into_iter()
         .for_each(|userdata_dir| {
-            if let Ok(config_lines) = open_text_config(&userdata_dir.path) {
-                let options = parse_vdf_keys("apps", config_lines, &parsers, None);
-                result.insert(userdata_dir.user_id.into(), options);
-            } else {
-                warn!(
-                    "Couldn't open file '{}'",
-                    userdata_dir.path.to_string_lossy()
-                )
+            match read_text_config(&userdata_dir.path).and_then(|c| vdf::parse(&c)) {
+                Ok(root) => {
+                    let options = parse_vdf_keys("apps", &root, &parsers, None);
+                    result.insert(userdata_dir.user_id.into(), options);
+                }
+                Err(e) => warn!(
+                    "Couldn't parse file '{}': {}",
+                    userdata_dir.path.to_string_lossy(),
+                    e
+                ),
             }
         });
 
     Ok(result)
 }
+
+/// localconfig.vdf of a user, which may not exist yet
+pub fn local_config_path(steam_home: &Path, user: &SteamId64) -> PathBuf {
+    root_dir(steam_home)
+        .join("userdata")
+        .join(UserId::from(user).to_string())
+        .join(CONFIG_PATH)
+}
+
+/// An app's launch options in localconfig.vdf. `None` if they've never been set.
+pub fn get_launch_options(config: &Document, app_id: &AppId) -> Result<Option<String>> {
+    let id = app_id.to_string();
+    let path = [&APPS_PATH[..], &[id.as_str(), "LaunchOptions"]].concat();
+
+    Ok(config
+        .root()?
+        .get_path(&path)
+        .and_then(vdf::Value::as_str)
+        .map(str::to_string))
+}
+
+/// Sets an app's launch options in localconfig.vdf. Steam stores cleared options as an
+/// empty string.
+/// ```vdf
+/// "apps"
+/// {
+///     "[app_id]"
+///     {
+///         "LaunchOptions"     "[options]"
+///     }
+/// }
+/// ```
+pub fn set_launch_options(config: &mut Document, app_id: &AppId, options: &str) -> Result<()> {
+    let id = app_id.to_string();
+
+    config.set(
+        &[&APPS_PATH[..], &[id.as_str(), "LaunchOptions"]].concat(),
+        options,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "\"UserLocalConfigStore\"\n{\n\t\"Software\"\n\t{\n\t\t\"Valve\"\n\t\t{\n\t\t\t\"Steam\"\n\t\t\t{\n\t\t\t\t\"apps\"\n\t\t\t\t{\n\t\t\t\t\t\"620\"\n\t\t\t\t\t{\n\t\t\t\t\t\t\"LastPlayed\"\t\t\"1637000000\"\n\t\t\t\t\t\t\"LaunchOptions\"\t\t\"mangohud \\\"%command%\\\"\"\n\t\t\t\t\t}\n\t\t\t\t}\n\t\t\t}\n\t\t}\n\t}\n}\n";
+
+    #[test]
+    fn set_launch_options_escapes_quotes() {
+        let mut config = Document::parse(CONFIG.to_string()).unwrap();
+
+        set_launch_options(&mut config, &AppId(620), r#"A="1 2" %command%"#).unwrap();
+
+        assert_eq!(
+            config.as_str(),
+            CONFIG.replace(r#""mangohud \"%command%\"""#, r#""A=\"1 2\" %command%""#)
+        );
+        assert_eq!(
+            get_launch_options(&config, &AppId(620)).unwrap().as_deref(),
+            Some(r#"A="1 2" %command%"#)
+        );
+    }
+
+    #[test]
+    fn set_launch_options_adds_new_app() {
+        let mut config = Document::parse(CONFIG.to_string()).unwrap();
+
+        set_launch_options(&mut config, &AppId(730), "-novid").unwrap();
+
+        assert_eq!(
+            get_launch_options(&config, &AppId(730)).unwrap().as_deref(),
+            Some("-novid")
+        );
+        assert_eq!(
+            get_launch_options(&config, &AppId(620)).unwrap().as_deref(),
+            Some(r#"mangohud "%command%""#)
+        );
+        assert!(config.as_str().contains("\"LastPlayed\"\t\t\"1637000000\""));
+    }
+}
@@ -0,0 +1,364 @@
+//! Steam installations on this machine. Each has a Steam home holding symlinks to the directory
+//! Steam is installed in, which it recreates on startup.
+//! ```text
+//! ~/.steam/                   [home]
+//!     root -> ~/.local/share/Steam
+//!     steam -> ~/.local/share/Steam
+//!     registry.vdf
+//! ```
+use super::process::find_steam_pid;
+use super::resolve_link;
+use log::{debug, warn};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const FLATPAK_DIR: &str = ".var/app/com.valvesoftware.Steam";
+const SNAP_DIR: &str = "snap/steam/common";
+const LINKS: [&str; 2] = ["root", "steam"];
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SteamInstall {
+    /// native, flatpak or snap
+    pub name: String,
+    /// Directory with the `root` and `steam` symlinks
+    pub home: PathBuf,
+    /// Directory Steam is installed in
+    pub root: PathBuf,
+}
+
+/// Where a kind of installation keeps its home, and where Steam may be installed
+struct Layout {
+    name: &'static str,
+    home: PathBuf,
+    roots: Vec<PathBuf>,
+}
+
+fn layouts(user_home: &Path, data_dir: &Path) -> Vec<Layout> {
+    let flatpak = user_home.join(FLATPAK_DIR);
+    let snap = user_home.join(SNAP_DIR);
+
+    vec![
+        Layout {
+            name: "native",
+            home: user_home.join(".steam"),
+            roots: vec![
+                data_dir.join("Steam"),
+                user_home.join(".local/share/Steam"),
+                user_home.join(".steam/debian-installation"),
+            ],
+        },
+        Layout {
+            name: "flatpak",
+            home: flatpak.join(".steam"),
+            roots: vec![
+                flatpak.join(".local/share/Steam"),
+                flatpak.join("data/Steam"),
+            ],
+        },
+        Layout {
+            name: "snap",
+            home: snap.join(".steam"),
+            roots: vec![snap.join(".local/share/Steam")],
+        },
+    ]
+}
+
+/// Whether Steam is installed in `path`
+fn is_steam_root(path: &Path) -> bool {
+    path.join("steamapps").is_dir() || path.join("config/config.vdf").is_file()
+}
+
+/// Whether `link` in `home` is missing, broken or points somewhere Steam isn't installed. Real
+/// directories are left alone.
+fn is_broken_link(home: &Path, link: &str) -> bool {
+    match fs::symlink_metadata(home.join(link)) {
+        Ok(meta) if meta.file_type().is_symlink() => {
+            !resolve_link(home, link).is_some_and(|target| is_steam_root(&target))
+        }
+        Ok(_) => false,
+        Err(_) => true,
+    }
+}
+
+/// Points a broken `link` at `root`
+fn repair_link(link: &Path, root: &Path) -> std::io::Result<()> {
+    match fs::remove_file(link) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    std::os::unix::fs::symlink(root, link)?;
+    warn!(
+        "Repaired '{}', it now links to '{}'",
+        link.display(),
+        root.display()
+    );
+
+    Ok(())
+}
+
+/// Finds where the layout's Steam is installed. Links are only followed into the layout's own
+/// install directories, otherwise those are probed directly. Real directories in the home are
+/// used as is. Broken links are only repaired if `repair` is set.
+fn locate(
+    layout: Layout,
+    repair: bool,
+    steam_running: impl Fn(&Path) -> bool,
+) -> Option<SteamInstall> {
+    let roots: Vec<PathBuf> = layout
+        .roots
+        .iter()
+        .filter_map(|root| root.canonicalize().ok())
+        .collect();
+    let linked = LINKS.iter().filter_map(|link| {
+        let target = resolve_link(&layout.home, link)?;
+        let is_link = fs::symlink_metadata(layout.home.join(link))
+            .is_ok_and(|meta| meta.file_type().is_symlink());
+        (!is_link || roots.iter().any(|root| target.starts_with(root))).then_some(target)
+    });
+    let root = linked
+        .chain(roots.iter().cloned())
+        .find(|root| is_steam_root(root))?;
+    debug!("Found {} Steam in '{}'", layout.name, root.display());
+
+    let broken: Vec<&str> = LINKS
+        .into_iter()
+        .filter(|link| is_broken_link(&layout.home, link))
+        .collect();
+    if !broken.is_empty() && !repair {
+        debug!("Not repairing the links in '{}'", layout.home.display());
+    } else if !broken.is_empty() && steam_running(&layout.home) {
+        // Steam recreates the links itself when it starts
+        warn!(
+            "Not repairing the links in '{}' while Steam is running",
+            layout.home.display()
+        );
+    } else if !broken.is_empty() {
+        let repaired = fs::create_dir_all(&layout.home).and_then(|_| {
+            broken
+                .iter()
+                .try_for_each(|link| repair_link(&layout.home.join(link), &root))
+        });
+        if let Err(e) = repaired {
+            warn!(
+                "Couldn't link '{}' to {} Steam in '{}': {}",
+                layout.home.display(),
+                layout.name,
+                root.display(),
+                e
+            );
+            return None;
+        }
+    }
+    if resolve_link(&layout.home, "root").as_ref() != Some(&root) {
+        warn!(
+            "'{}' isn't a link to {} Steam in '{}'",
+            layout.home.join("root").display(),
+            layout.name,
+            root.display()
+        );
+        return None;
+    }
+
+    Some(SteamInstall {
+        name: layout.name.to_string(),
+        home: layout.home,
+        root,
+    })
+}
+
+/// Installations under `user_home`. Broken symlinks are repaired if `repair` is set, unless
+/// Steam is running. `data_dir` is `$XDG_DATA_HOME`.
+pub fn find_installs_in(user_home: &Path, data_dir: &Path, repair: bool) -> Vec<SteamInstall> {
+    let mut installs: Vec<SteamInstall> = Vec::new();
+    for install in layouts(user_home, data_dir)
+        .into_iter()
+        .filter_map(|layout| locate(layout, repair, |home| find_steam_pid(home).is_some()))
+    {
+        if !installs.iter().any(|i| i.root == install.root) {
+            installs.push(install);
+        }
+    }
+
+    installs
+}
+
+/// Native, Flatpak and Snap installations of the current user. Only commands that change
+/// Steam's config should `repair` broken links.
+pub fn find_installs(repair: bool) -> Vec<SteamInstall> {
+    let Some(user_home) = dirs::home_dir() else {
+        warn!("Couldn't find the home directory");
+        return Vec::new();
+    };
+    let data_dir = dirs::data_dir().unwrap_or_else(|| user_home.join(".local/share"));
+
+    find_installs_in(&user_home, &data_dir, repair)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repairs_broken_root_link() {
+        let temp = tempfile::tempdir().unwrap();
+        let home = temp.path().canonicalize().unwrap();
+        let root = home.join(".local/share/Steam");
+        fs::create_dir_all(root.join("steamapps")).unwrap();
+        fs::create_dir_all(home.join(".steam")).unwrap();
+        std::os::unix::fs::symlink(home.join("gone"), home.join(".steam/root")).unwrap();
+
+        let installs = find_installs_in(&home, &home.join(".local/share"), true);
+
+        assert_eq!(
+            installs,
+            [SteamInstall {
+                name: "native".to_string(),
+                home: home.join(".steam"),
+                root: root.clone(),
+            }]
+        );
+        assert_eq!(home.join(".steam/root").canonicalize().unwrap(), root);
+        assert_eq!(home.join(".steam/steam").canonicalize().unwrap(), root);
+    }
+
+    #[test]
+    fn finds_flatpak_and_snap() {
+        let temp = tempfile::tempdir().unwrap();
+        let home = temp.path().canonicalize().unwrap();
+        let flatpak = home.join(FLATPAK_DIR).join(".local/share/Steam");
+        let snap = home.join(SNAP_DIR).join(".local/share/Steam");
+        fs::create_dir_all(flatpak.join("steamapps")).unwrap();
+        fs::create_dir_all(snap.join("steamapps")).unwrap();
+
+        let installs = find_installs_in(&home, &home.join(".local/share"), true);
+        let names: Vec<&str> = installs.iter().map(|i| i.name.as_str()).collect();
+
+        assert_eq!(names, ["flatpak", "snap"]);
+        assert_eq!(installs[0].root, flatpak);
+        assert!(!home.join(".steam").exists());
+    }
+
+    #[test]
+    fn leaves_real_directories_alone() {
+        let temp = tempfile::tempdir().unwrap();
+        let home = temp.path().canonicalize().unwrap();
+        let root = home.join(".steam/root");
+        fs::create_dir_all(root.join("steamapps")).unwrap();
+
+        let installs = find_installs_in(&home, &home.join(".local/share"), true);
+
+        assert_eq!(installs[0].root, root);
+        assert!(!fs::symlink_metadata(&root)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert!(fs::symlink_metadata(home.join(".steam/steam"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+    }
+
+    #[test]
+    fn doesnt_repair_while_steam_is_running() {
+        let temp = tempfile::tempdir().unwrap();
+        let home = temp.path().canonicalize().unwrap();
+        fs::create_dir_all(home.join(".local/share/Steam/steamapps")).unwrap();
+        fs::create_dir_all(home.join(".steam")).unwrap();
+        std::os::unix::fs::symlink(home.join("gone"), home.join(".steam/root")).unwrap();
+        let layout = layouts(&home, &home.join(".local/share")).remove(0);
+
+        assert_eq!(locate(layout, true, |_| true), None);
+        assert!(home.join(".steam/root").canonicalize().is_err());
+        assert!(!home.join(".steam/steam").exists());
+    }
+
+    #[test]
+    fn leaves_valid_links_alone() {
+        let temp = tempfile::tempdir().unwrap();
+        let home = temp.path().canonicalize().unwrap();
+        let root = home.join(".local/share/Steam");
+        fs::create_dir_all(root.join("steamapps")).unwrap();
+        let layout = layouts(&home, &home.join(".local/share")).remove(0);
+        fs::create_dir_all(&layout.home).unwrap();
+        for link in LINKS {
+            std::os::unix::fs::symlink(&root, layout.home.join(link)).unwrap();
+        }
+
+        let install = locate(layout, true, |_| panic!("Links don't need repairing")).unwrap();
+
+        assert_eq!(install.root, root);
+    }
+
+    #[test]
+    fn follows_flatpak_links_inside_its_sandbox() {
+        let temp = tempfile::tempdir().unwrap();
+        let home = temp.path().canonicalize().unwrap();
+        let native = home.join(".local/share/Steam");
+        let flatpak = home.join(FLATPAK_DIR).join(".local/share/Steam");
+        for (steam_home, root) in [
+            (home.join(".steam"), &native),
+            (home.join(FLATPAK_DIR).join(".steam"), &flatpak),
+        ] {
+            fs::create_dir_all(root.join("steamapps")).unwrap();
+            fs::create_dir_all(&steam_home).unwrap();
+            // Flatpak Steam links to the same path as native Steam, but inside its sandbox
+            for link in LINKS {
+                std::os::unix::fs::symlink(&native, steam_home.join(link)).unwrap();
+            }
+        }
+
+        let installs = find_installs_in(&home, &home.join(".local/share"), false);
+
+        assert_eq!(
+            installs,
+            [
+                SteamInstall {
+                    name: "native".to_string(),
+                    home: home.join(".steam"),
+                    root: native.clone(),
+                },
+                SteamInstall {
+                    name: "flatpak".to_string(),
+                    home: home.join(FLATPAK_DIR).join(".steam"),
+                    root: flatpak.clone(),
+                }
+            ]
+        );
+        assert_eq!(crate::steam::root_dir(&installs[1].home), flatpak);
+        assert_eq!(
+            fs::read_link(home.join(FLATPAK_DIR).join(".steam/root")).unwrap(),
+            native
+        );
+    }
+
+    #[test]
+    fn only_follows_links_into_the_layouts_roots() {
+        let temp = tempfile::tempdir().unwrap();
+        let home = temp.path().canonicalize().unwrap();
+        let native = home.join(".local/share/Steam");
+        let snap = home.join(SNAP_DIR).join(".local/share/Steam");
+        fs::create_dir_all(native.join("steamapps")).unwrap();
+        fs::create_dir_all(snap.join("steamapps")).unwrap();
+        let layout = layouts(&home, &home.join(".local/share")).remove(2);
+        fs::create_dir_all(&layout.home).unwrap();
+        for link in LINKS {
+            std::os::unix::fs::symlink(&native, layout.home.join(link)).unwrap();
+        }
+
+        assert_eq!(locate(layout, false, |_| false), None);
+    }
+
+    #[test]
+    fn doesnt_repair_unless_asked() {
+        let temp = tempfile::tempdir().unwrap();
+        let home = temp.path().canonicalize().unwrap();
+        fs::create_dir_all(home.join(".local/share/Steam/steamapps")).unwrap();
+        fs::create_dir_all(home.join(".steam")).unwrap();
+        std::os::unix::fs::symlink(home.join("gone"), home.join(".steam/root")).unwrap();
+
+        assert!(find_installs_in(&home, &home.join(".local/share"), false).is_empty());
+        assert!(home.join(".steam/root").canonicalize().is_err());
+        assert!(!home.join(".steam/steam").exists());
+    }
+}
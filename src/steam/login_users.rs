@@ -1,21 +1,59 @@
-use super::{Result, SteamId64};
-use crate::open_text_config;
+use super::{root_dir, vdf, Result, SteamId64};
+use crate::read_text_config;
 use std::path::Path;
 
-fn parse_display_name(id: &SteamId64, config_lines: impl Iterator<Item = String>) -> String {
-    config_lines
-        .skip_while(|line| line.trim_start() != format!("\"{}\"", id))
-        .find(|line| line.trim_start().starts_with("\"PersonaName"))
-        .and_then(|line| line.rsplit_terminator('"').next().map(|s| s.to_string()))
+fn parse_display_name(id: &SteamId64, root: &vdf::Object) -> String {
+    root.get_path(&["users", &id.to_string(), "PersonaName"])
+        .and_then(vdf::Value::as_str)
+        .map(|s| s.to_string())
         .unwrap_or_else(|| id.to_string())
 }
 
-pub fn get_display_name(steam_home: &Path, id: &SteamId64) -> Result<String> {
-    const CONFIG_PATH: &str = "root/config/loginusers.vdf";
-    let lines = open_text_config(steam_home.join(CONFIG_PATH))
+/// The user flagged `MostRecent`. While Steam is running, this is the logged in user.
+fn parse_most_recent(root: &vdf::Object) -> Option<SteamId64> {
+    root.get_object("users")?
+        .iter()
+        .find(|(_, user)| {
+            user.as_object()
+                .and_then(|user| user.get("MostRecent"))
+                .and_then(vdf::Value::as_str)
+                == Some("1")
+        })
+        .and_then(|(id, _)| id.parse().ok())
+        .map(SteamId64)
+}
+
+/// The user whose persona name matches `name`, ignoring case
+fn parse_user_by_name(name: &str, root: &vdf::Object) -> Option<SteamId64> {
+    root.get_object("users")?
+        .iter()
+        .find(|(_, user)| {
+            user.as_object()
+                .and_then(|user| user.get("PersonaName"))
+                .and_then(vdf::Value::as_str)
+                .is_some_and(|persona| persona.eq_ignore_ascii_case(name))
+        })
+        .and_then(|(id, _)| id.parse().ok())
+}
+
+fn read_login_users(steam_home: &Path) -> Result<vdf::Object> {
+    const CONFIG_PATH: &str = "config/loginusers.vdf";
+    let config = read_text_config(root_dir(steam_home).join(CONFIG_PATH))
         .map_err(|e| format!("Couldn't open file '{}': {}", CONFIG_PATH, e))?;
 
-    Ok(parse_display_name(id, lines))
+    vdf::parse(&config).map_err(|e| format!("Couldn't parse file '{}': {}", CONFIG_PATH, e).into())
+}
+
+pub fn get_display_name(steam_home: &Path, id: &SteamId64) -> Result<String> {
+    Ok(parse_display_name(id, &read_login_users(steam_home)?))
+}
+
+pub fn get_most_recent_user(steam_home: &Path) -> Result<Option<SteamId64>> {
+    Ok(parse_most_recent(&read_login_users(steam_home)?))
+}
+
+pub fn find_user_by_name(steam_home: &Path, name: &str) -> Result<Option<SteamId64>> {
+    Ok(parse_user_by_name(name, &read_login_users(steam_home)?))
 }
 
 #[cfg(test)]
@@ -26,7 +64,8 @@ mod tests {
 
     #[test]
     fn can_parse_display_name() {
-        let lines = r#"
+        let root = vdf::parse(
+            r#"
             "users"
             {
                 "123"
@@ -52,18 +91,66 @@ mod tests {
                     "Timestamp"		"123456789"
                 }
             }
-            "#
-        .lines()
-        .map(|s| s.to_string());
+            "#,
+        )
+        .unwrap();
 
-        let display_name = parse_display_name(&ID, lines);
+        let display_name = parse_display_name(&ID, &root);
 
         assert_eq!(display_name, "Display Name1", "")
     }
 
+    #[test]
+    fn finds_most_recent_user() {
+        let root = vdf::parse(
+            r#"
+            "users"
+            {
+                "123"
+                {
+                    "PersonaName"		"Display Name1"
+                    "MostRecent"		"0"
+                }
+                "12345678901234567"
+                {
+                    "PersonaName"		"Display Name2"
+                    "MostRecent"		"1"
+                }
+            }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(parse_most_recent(&root), Some(ID));
+    }
+
+    #[test]
+    fn finds_user_by_persona_name() {
+        let root = vdf::parse(
+            r#"
+            "users"
+            {
+                "123"
+                {
+                    "PersonaName"		"Display Name1"
+                }
+                "12345678901234567"
+                {
+                    "PersonaName"		"Display Name2"
+                }
+            }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(parse_user_by_name("display name2", &root), Some(ID));
+        assert_eq!(parse_user_by_name("Display Name3", &root), None);
+    }
+
     #[test]
     fn defaults_to_id_if_no_persona_name() {
-        let lines = r#"
+        let root = vdf::parse(
+            r#"
             "users"
             {
                 "12345678901234567"
@@ -77,18 +164,19 @@ mod tests {
                     "Timestamp"		"123456789"
                 }
             }
-            "#
-        .lines()
-        .map(|s| s.to_string());
+            "#,
+        )
+        .unwrap();
 
-        let display_name = parse_display_name(&ID, lines);
+        let display_name = parse_display_name(&ID, &root);
 
         assert_eq!(display_name, ID.to_string(), "")
     }
 
     #[test]
     fn defaults_to_id_if_no_id_match() {
-        let lines = r#"
+        let root = vdf::parse(
+            r#"
             "users"
             {
                 "123"
@@ -103,11 +191,11 @@ mod tests {
                     "Timestamp"		"123456789"
                 }
             }
-            "#
-        .lines()
-        .map(|s| s.to_string());
+            "#,
+        )
+        .unwrap();
 
-        let display_name = parse_display_name(&ID, lines);
+        let display_name = parse_display_name(&ID, &root);
 
         assert_eq!(display_name, ID.to_string(), "")
     }
@@ -1,32 +1,66 @@
 pub mod app_info;
+pub mod app_manifest;
+pub mod bin_vdf;
+pub mod compat_data;
 mod compat_tool;
+pub mod installed_tools;
+pub mod launch_options;
+pub mod library_folders;
 mod local_config;
+pub mod locator;
 mod login_users;
+pub mod process;
 pub mod registry;
 pub mod shortcuts;
+pub mod vdf;
+pub mod vdf_edit;
 
-pub use self::compat_tool::parse_compat_tool_mapping;
-pub use self::local_config::parse_launch_options_mapping;
-pub use self::login_users::get_display_name;
+pub use self::compat_tool::{parse_compat_tool_mapping, set_compat_tool, CompatToolMapping};
+pub use self::local_config::{
+    get_launch_options, local_config_path, parse_launch_options_mapping, set_launch_options,
+};
+pub use self::login_users::{find_user_by_name, get_display_name, get_most_recent_user};
 use derive_more::{Constructor, Display, FromStr};
+use serde::{Serialize, Serializer};
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
-#[derive(Constructor, Display, FromStr, Hash, Eq, PartialEq, Copy, Clone, Debug)]
+#[derive(
+    Constructor,
+    Display,
+    FromStr,
+    Hash,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Copy,
+    Clone,
+    Debug,
+    Serialize,
+)]
+#[serde(transparent)]
 pub struct AppId(u64);
-#[derive(Display, Hash, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Display, FromStr, Hash, Eq, PartialEq, Ord, PartialOrd, Debug)]
 pub struct UserId(u32);
 
-#[derive(Display, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Display, FromStr, Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Debug)]
 pub struct SteamId64(u64);
 
+/// Serialized as a string since 64 bit ids lose precision in most JSON parsers
+impl Serialize for SteamId64 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 impl From<UserId> for SteamId64 {
     fn from(id: UserId) -> Self {
         // https://developer.valvesoftware.com/wiki/SteamID
         let id = id.0 as u64;
-        let (y, z): (u64, u64) = if id % 2 == 0 {
+        let (y, z): (u64, u64) = if id.is_multiple_of(2) {
             (0, id / 2)
         } else {
             (1, (id - 1) / 2)
@@ -36,13 +70,69 @@ impl From<UserId> for SteamId64 {
     }
 }
 
+impl From<&SteamId64> for UserId {
+    /// The account id, which names the user's userdata directory
+    fn from(id: &SteamId64) -> Self {
+        UserId(id.0 as u32)
+    }
+}
+
 struct UserDataFile {
     pub path: PathBuf,
     pub user_id: UserId,
 }
-const DEFAULT_PROTON_APP_ID: AppId = AppId(0);
+/// App id Steam stores the tool for all other titles under
+pub const DEFAULT_PROTON_APP_ID: AppId = AppId(0);
+
+/// Flatpak's sandbox directory and the user's home, if `steam_home` is inside a Flatpak sandbox.
+/// Steam in the sandbox sees the sandbox directory as the user's home.
+fn flatpak_sandbox(steam_home: &Path) -> Option<(&Path, &Path)> {
+    let sandbox = steam_home.parent()?;
+    let apps = sandbox.parent()?;
+    if !apps.ends_with(".var/app") {
+        return None;
+    }
+
+    Some((sandbox, apps.parent()?.parent()?))
+}
+
+/// Where `link` in the Steam home leads. Flatpak Steam writes absolute links that are only
+/// valid inside its sandbox, so those are followed as Steam would follow them.
+pub fn resolve_link(steam_home: &Path, link: &str) -> Option<PathBuf> {
+    let path = steam_home.join(link);
+    let in_sandbox = std::fs::read_link(&path).ok().and_then(|target| {
+        let (sandbox, user_home) = flatpak_sandbox(steam_home)?;
+        if target.starts_with(sandbox) {
+            return None;
+        }
+        Some(sandbox.join(target.strip_prefix(user_home).ok()?))
+    });
+
+    in_sandbox.unwrap_or(path).canonicalize().ok()
+}
+
+/// Directory Steam is installed in, through the Steam home's `root` link
+pub fn root_dir(steam_home: &Path) -> PathBuf {
+    resolve_link(steam_home, "root").unwrap_or_else(|| steam_home.join("root"))
+}
+
+/// Distinct Steam installation directories linked from the Steam home
+fn steam_roots(steam_home: &Path) -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    for root in ["root", "steam"]
+        .iter()
+        .filter_map(|link| resolve_link(steam_home, link))
+    {
+        if !roots.contains(&root) {
+            roots.push(root);
+        }
+    }
+
+    roots
+}
+
 fn get_userdata_file(steam_home: &Path, relative_file_path: &str) -> Result<Vec<UserDataFile>> {
-    let userdata_path = steam_home.join("root/userdata");
+    let userdata_path = root_dir(steam_home).join("userdata");
     let result: Vec<UserDataFile> = std::fs::read_dir(&userdata_path)
         .map_err(|e| {
             format!(
@@ -55,7 +145,7 @@ fn get_userdata_file(steam_home: &Path, relative_file_path: &str) -> Result<Vec<
         .filter_map(|entry| entry.path().is_dir().then(|| entry.path()))
         .filter_map(|dir| {
             dir.components()
-                .last()
+                .next_back()
                 .map(|component| component.as_os_str().to_str().unwrap_or(""))
                 .map(|str| str.parse::<u32>().unwrap_or(0))
                 .filter(|user_id| *user_id != 0)
@@ -77,57 +167,10 @@ fn get_userdata_file(steam_home: &Path, relative_file_path: &str) -> Result<Vec<
     Ok(result)
 }
 
-fn parse_names_from_bin_vdf(
-    file_contents: &[u8],
-    possible_keys: &[&str],
-    whitelist: &[&AppId],
-) -> HashMap<AppId, String> {
-    let mut map = HashMap::new();
-    const ID_KEY: &[u8; 6] = b"appid\0";
-
-    for app_id in whitelist.iter() {
-        let id = ID_KEY
-            .iter()
-            .chain(&(app_id.0 as u32).to_le_bytes())
-            .copied()
-            .collect::<Vec<u8>>();
-
-        let name = &file_contents
-            .windows(id.len())
-            .position(|window| window == id)
-            .and_then(|i| {
-                let start = i + id.len() + 1; // index + "appname\0appid"
-                let current = &file_contents[start..];
-
-                possible_keys
-                    .iter()
-                    .map(|&key| (key.to_owned() + "\0").as_bytes().to_owned())
-                    .filter_map(|key| {
-                        current
-                            .windows(key.len())
-                            .position(|window| window == key)
-                            .map(|i| {
-                                let start = i + key.len(); // index + "value\0"
-                                let current = &current[start..];
-                                let end = current.iter().position(|&b| b == 0).unwrap();
-                                let name = &current[..end];
-                                String::from_utf8_lossy(name).into_owned()
-                            })
-                    })
-                    .next()
-            });
-
-        if let Some(name) = name {
-            map.insert(*app_id.to_owned(), name.to_owned());
-        }
-    }
-
-    map
-}
-
 type KeyParser<T> = fn(&str, &AppId, &mut T);
 
-/// Super fragile parsing. May have unexpected results if the vdf is malformed.
+/// Calls the matching key parser for each string value of every app in `section`.
+/// The first object named `section` is used, no matter how deeply it's nested.
 /// ```vdf
 /// ...
 /// "[section]"
@@ -144,51 +187,33 @@ type KeyParser<T> = fn(&str, &AppId, &mut T);
 /// ```
 fn parse_vdf_keys<T>(
     section: &str,
-    config_lines: impl Iterator<Item = String>,
+    root: &vdf::Object,
     key_parsers: &HashMap<&str, KeyParser<T>>,
     whitelist: Option<&HashSet<&AppId>>,
 ) -> T
 where
     T: Default,
 {
-    let mut lines = config_lines
-        .skip_while(|l| !l.trim().eq_ignore_ascii_case(&format!("\"{}\"", section)))
-        .skip(2);
     let mut result = T::default();
-    let mut depth = 0;
-    let mut app_id: Option<AppId> = None;
-
-    while let (Some(line), true) = (lines.next(), depth > -1) {
-        let line = line.trim();
-        if line == "{" {
-            depth += 1;
-        } else if line == "}" {
-            depth -= 1;
-            app_id = None;
-        } else if let Ok(id) = line.trim_matches('"').parse() {
-            if let Some(whitelist) = whitelist {
-                if whitelist.contains(&id) {
-                    app_id = Some(id);
+    let apps = root
+        .find_object(section)
+        .into_iter()
+        .flat_map(|section| section.iter())
+        .filter_map(|(key, value)| Some((key.parse::<AppId>().ok()?, value.as_object()?)))
+        .filter(|(id, _)| whitelist.is_none_or(|w| w.contains(id)));
+
+    for (app_id, app) in apps {
+        for (key, value) in app.iter() {
+            let parse = key_parsers
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case(key))
+                .map(|(_, parse)| parse);
+            match (parse, value.as_str()) {
+                (Some(parse), Some(value)) if !value.is_empty() => {
+                    parse(value, &app_id, &mut result)
                 }
-            } else {
-                app_id = Some(id);
+                _ => {}
             }
-        } else if let Some(app_id) = app_id {
-            key_parsers
-                .iter()
-                .filter(|(key, _)| {
-                    line.to_lowercase()
-                        .starts_with(&format!("\"{}\"", key.to_lowercase()))
-                })
-                .filter_map(|(_, parse)| {
-                    line.splitn(4, '"')
-                        .last()
-                        .map(|s| (&s[..s.len() - 1], parse))
-                        .filter(|(s, _)| !s.is_empty())
-                })
-                .for_each(|(value, parse)| {
-                    parse(value, &app_id, &mut result);
-                });
         }
     }
 
@@ -199,50 +224,25 @@ where
 mod tests {
     use super::*;
 
-    #[test]
-    fn bin_vdf_uses_first_potential_key() {
-        let contents = [
-            0x00u8, 0x73, 0x68, 0x6F, 0x72, 0x74, 0x63, 0x75, 0x74, 0x73, 0x00, 0x00, 0x30, 0x00,
-            0x02, 0x61, 0x70, 0x70, 0x69, 0x64, 0x00, // appid\0
-            0x6E, 0xB1, 0xFE, 0x99, 0x01, // 2583605614 (little endian u32)
-            0x61, 0x70, 0x70, 0x6E, 0x61, 0x6D, 0x65, 0x00, // appname\0
-            0x54, 0x68, 0x65, 0x20, 0x4e, 0x61, 0x6d, 0x65, 0x31, 0x00, // The Name1\0
-            0x01, 0x65, 0x78, 0x65, 0x00, // .exe\0
-            0x02, 0x61, 0x70, 0x70, 0x69, 0x64, 0x00, // appid\0
-            0x6E, 0xB1, 0xFE, 0x98, 0x01, // 2566828398 (little endian u32)
-            0x41, 0x70, 0x70, 0x4E, 0x61, 0x6D, 0x65, 0x00, // AppName\0
-            0x54, 0x68, 0x65, 0x20, 0x4e, 0x61, 0x6d, 0x65, 0x32, 0x00, // The Name2\0
-            0x01, 0x65, 0x78, 0x65, 0x00, // .exe\0
-        ];
-        let app_id1 = &AppId::new(2583605614);
-        let app_id2 = &AppId::new(2566828398);
-        let keys = ["appname", "AppName"];
-
-        let result = parse_names_from_bin_vdf(&contents, &keys, &[app_id1, app_id2]);
-
-        assert_eq!(result.len(), 2);
-        assert_eq!(result.get(app_id1).unwrap(), "The Name1");
-        assert_eq!(result.get(app_id2).unwrap(), "The Name2");
-    }
-
     #[test]
     fn text_vdf_parsing_is_case_insensitive() {
-        let lines = r#"
+        let root = vdf::parse(
+            r#"
             "Section"
             {
                 "12345"
                 {
                     "Asdf" "0"
                 }
-            }"#
-        .lines()
-        .map(|s| s.to_string());
+            }"#,
+        )
+        .unwrap();
         fn parse(_: &str, _: &AppId, result: &mut u32) {
-            *result = *result + 1;
+            *result += 1;
         }
         let parsers = HashMap::from([("aSdF", parse as KeyParser<u32>)]);
 
-        let result = parse_vdf_keys("sEcTiOn", lines, &parsers, None);
+        let result = parse_vdf_keys("sEcTiOn", &root, &parsers, None);
 
         assert_eq!(
             result, 1,
@@ -251,28 +251,34 @@ mod tests {
     }
 
     #[test]
-    fn text_vdf_parsing_includes_escaped_chars() {
-        let lines = r#"
+    fn steam_id_round_trips_account_id() {
+        let id = SteamId64::from(UserId(1880504));
+
+        assert_eq!(id, SteamId64(76561197962146232));
+        assert_eq!(UserId::from(&id), UserId(1880504));
+    }
+
+    #[test]
+    fn text_vdf_parsing_unescapes_chars() {
+        let root = vdf::parse(
+            r#"
             "Section"
             {
                 "12345"
                 {
                     "Asdf" "\"0\""
                 }
-            }"#
-        .lines()
-        .map(|s| s.to_string());
+            }"#,
+        )
+        .unwrap();
         fn parse(str: &str, _: &AppId, result: &mut String) {
             *result = str.to_string();
         }
         let parsers = HashMap::from([("Asdf", parse as KeyParser<String>)]);
 
-        let result = parse_vdf_keys("Section", lines, &parsers, None);
+        let result = parse_vdf_keys("Section", &root, &parsers, None);
 
-        assert_eq!(
-            result, "\\\"0\\\"",
-            "Parsing value should include escaped chars"
-        );
+        assert_eq!(result, "\"0\"", "Parsing value should unescape chars");
     }
 
     #[test]
@@ -0,0 +1,81 @@
+//! Detection of a running Steam client. Steam rewrites its config files when it exits, so
+//! any changes made while it's running are lost.
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+const PROC_ROOT: &str = "/proc";
+
+/// Whether `pid` is the Steam client (or its launcher script)
+fn is_steam(proc_root: &Path, pid: u32) -> bool {
+    fs::read_to_string(proc_root.join(pid.to_string()).join("comm"))
+        .is_ok_and(|comm| comm.trim_end() == "steam")
+}
+
+fn find_pid(steam_home: &Path, proc_root: &Path) -> Option<u32> {
+    let pid_file = fs::read_to_string(steam_home.join("steam.pid"))
+        .ok()
+        .and_then(|pid| pid.trim().parse().ok());
+
+    // steam.pid is left behind when Steam crashes, so it needs to be verified
+    pid_file
+        .filter(|pid| is_steam(proc_root, *pid))
+        .or_else(|| {
+            fs::read_dir(proc_root)
+                .ok()?
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+                .find(|pid| is_steam(proc_root, *pid))
+        })
+}
+
+/// Pid of the running Steam client. Checks `steam.pid` in the Steam home first, then
+/// searches every process.
+pub fn find_steam_pid(steam_home: &Path) -> Option<u32> {
+    find_pid(steam_home, Path::new(PROC_ROOT))
+}
+
+/// Blocks until Steam is no longer running
+pub fn wait_for_exit(steam_home: &Path, poll_interval: Duration) {
+    while find_steam_pid(steam_home).is_some() {
+        thread::sleep(poll_interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn fake_proc(processes: &[(u32, &str)]) -> TempDir {
+        let root = tempfile::tempdir().unwrap();
+        for (pid, comm) in processes {
+            let dir = root.path().join(pid.to_string());
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("comm"), format!("{}\n", comm)).unwrap();
+        }
+        fs::create_dir_all(root.path().join("self")).unwrap();
+
+        root
+    }
+
+    #[test]
+    fn finds_steam_by_process_name() {
+        let proc_root = fake_proc(&[(1, "systemd"), (42, "steamwebhelper"), (77, "steam")]);
+
+        assert_eq!(
+            find_pid(Path::new("/nonexistent"), proc_root.path()),
+            Some(77)
+        );
+    }
+
+    #[test]
+    fn ignores_stale_pid_file() {
+        let proc_root = fake_proc(&[(10, "bash")]);
+        let proc_root = proc_root.path();
+        fs::write(proc_root.join("steam.pid"), "10\n").unwrap();
+
+        assert_eq!(find_pid(proc_root, proc_root), None);
+    }
+}
@@ -1,4 +1,4 @@
-use super::{parse_vdf_keys, AppId, KeyParser};
+use super::{parse_vdf_keys, vdf, AppId, KeyParser, Result, UserId};
 use std::collections::{HashMap, HashSet};
 
 #[derive(Default, Debug)]
@@ -22,16 +22,29 @@ fn parse_name(name: &str, app_id: &AppId, registry: &mut Registry) {
     registry.app_names.insert(*app_id, name.to_string());
 }
 
-pub fn parse_registry(
-    config_lines: impl Iterator<Item = String>,
-    whitelist: &HashSet<&AppId>,
-) -> Registry {
+pub fn parse_registry(config: &str, whitelist: &HashSet<&AppId>) -> Result<Registry> {
     let parsers = HashMap::from([
         ("installed", parse_installed as KeyParser<Registry>),
         ("name", parse_name as KeyParser<Registry>),
     ]);
 
-    parse_vdf_keys("apps", config_lines, &parsers, Some(whitelist))
+    let root = vdf::parse(config)?;
+
+    Ok(parse_vdf_keys("apps", &root, &parsers, Some(whitelist)))
+}
+
+/// Account id of the user logged in to the running Steam client. Steam resets it to 0
+/// when it exits.
+pub fn parse_active_user(config: &str) -> Result<Option<UserId>> {
+    let root = vdf::parse(config)?;
+    let active_user = root
+        .find_object("ActiveProcess")
+        .and_then(|process| process.get("ActiveUser"))
+        .and_then(vdf::Value::as_u64)
+        .filter(|id| *id != 0)
+        .map(|id| UserId(id as u32));
+
+    Ok(active_user)
 }
 
 #[cfg(test)]
@@ -41,7 +54,7 @@ mod tests {
     fn treats_0_as_not_installed() {
         let app_id = AppId::new(12345);
         let filter = HashSet::from([&app_id]);
-        let lines = r#"
+        let config = r#"
             "apps"
             {
                 "12345"
@@ -50,11 +63,9 @@ mod tests {
                     "Updating"		"0"
                     "Running"		"0"
                 }
-            }"#
-        .lines()
-        .map(|s| s.to_string());
+            }"#;
 
-        let registry = super::parse_registry(lines, &filter);
+        let registry = super::parse_registry(config, &filter).unwrap();
         let installed_entry = registry.installed_apps.get(&app_id);
 
         assert!(installed_entry.is_none());
@@ -64,7 +75,7 @@ mod tests {
     fn treats_1_as_installed() {
         let app_id = AppId::new(12345);
         let filter = HashSet::from([&app_id]);
-        let lines = r#"
+        let config = r#"
             "apps"
             {
                 "12345"
@@ -73,11 +84,9 @@ mod tests {
                     "Updating"		"0"
                     "Running"		"0"
                 }
-            }"#
-        .lines()
-        .map(|s| s.to_string());
+            }"#;
 
-        let registry = super::parse_registry(lines, &filter);
+        let registry = super::parse_registry(config, &filter).unwrap();
         let installed_entry = registry.installed_apps.get(&app_id);
 
         assert!(installed_entry.is_some());
@@ -87,7 +96,7 @@ mod tests {
     fn name_is_some_if_kvp_present() {
         let app_id = AppId::new(12345);
         let filter = HashSet::from([&app_id]);
-        let lines = r#"
+        let config = r#"
             "apps"
             {
                 "12345"
@@ -97,11 +106,9 @@ mod tests {
                     "Running"		"0"
                     "name"  	"asdf"
                 }
-            }"#
-        .lines()
-        .map(|s| s.to_string());
+            }"#;
 
-        let registry = super::parse_registry(lines, &filter);
+        let registry = super::parse_registry(config, &filter).unwrap();
         let entry = registry.app_names.get(&app_id);
 
         assert!(entry.is_some());
@@ -112,7 +119,7 @@ mod tests {
     fn name_is_none_if_kvp_not_present() {
         let app_id = AppId::new(12345);
         let filter = HashSet::from([&app_id]);
-        let lines = r#"
+        let config = r#"
             "apps"
             {
                 "12345"
@@ -121,13 +128,52 @@ mod tests {
                     "Updating"		"0"
                     "Running"		"0"
                 }
-            }"#
-        .lines()
-        .map(|s| s.to_string());
+            }"#;
 
-        let registry = super::parse_registry(lines, &filter);
+        let registry = super::parse_registry(config, &filter).unwrap();
         let entry = registry.app_names.get(&app_id);
 
         assert!(entry.is_none());
     }
+
+    fn active_process(active_user: &str) -> String {
+        format!(
+            r#"
+            "Registry"
+            {{
+                "HKCU"
+                {{
+                    "Software"
+                    {{
+                        "Valve"
+                        {{
+                            "Steam"
+                            {{
+                                "ActiveProcess"
+                                {{
+                                    "pid"		"1234"
+                                    "ActiveUser"		"{}"
+                                }}
+                            }}
+                        }}
+                    }}
+                }}
+            }}"#,
+            active_user
+        )
+    }
+
+    #[test]
+    fn can_parse_active_user() {
+        let user = parse_active_user(&active_process("1880504")).unwrap();
+
+        assert_eq!(user, Some(UserId(1880504)));
+    }
+
+    #[test]
+    fn active_user_is_none_when_logged_out() {
+        let user = parse_active_user(&active_process("0")).unwrap();
+
+        assert!(user.is_none());
+    }
 }
@@ -1,25 +1,226 @@
-use super::{get_userdata_file, parse_names_from_bin_vdf, AppId, Result};
+use super::vdf::{Object, Value};
+use super::{bin_vdf, get_userdata_file, AppId, Result, SteamId64};
 use log::error;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 
-const POSSIBLE_KEYS: [&str; 2] = ["appname", "AppName"];
+/// A non-Steam game added to a user's library
+#[derive(Clone, Debug, PartialEq)]
+pub struct Shortcut {
+    pub app_id: AppId,
+    pub name: String,
+    pub exe: String,
+    pub start_dir: String,
+    pub icon: String,
+    pub launch_options: String,
+    pub is_hidden: bool,
+    pub allow_overlay: bool,
+    pub open_vr: bool,
+    /// Unix timestamp. 0 if never played.
+    pub last_play_time: u32,
+    pub tags: Vec<String>,
+}
+
+/// Bitwise CRC-32 (IEEE)
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, &b| {
+        (0..8).fold(crc ^ b as u32, |crc, _| {
+            (crc >> 1) ^ (0xEDB88320 & (!(crc & 1)).wrapping_add(1))
+        })
+    })
+}
+
+/// Shortcut app ids always have the high bit of their lower 32 bits set, which no Steam app does
+pub fn is_shortcut_id(app_id: &AppId) -> bool {
+    app_id.0 & 0x80000000 != 0
+}
+
+/// Older shortcuts don't store an app id. Steam derives it from the exe and name instead.
+fn legacy_app_id(exe: &str, name: &str) -> AppId {
+    let crc = crc32(format!("{}{}", exe, name).as_bytes());
+    AppId::new((crc | 0x80000000) as u64)
+}
+
+fn parse_shortcut(shortcut: &Object) -> Shortcut {
+    let string = |key| {
+        shortcut
+            .get(key)
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string()
+    };
+    let number = |key| shortcut.get(key).and_then(Value::as_u64).unwrap_or(0);
+    let name = string("AppName");
+    let exe = string("Exe");
 
-pub fn parse_names(steam_home: &Path, app_ids: &[&AppId]) -> Result<HashMap<AppId, String>> {
-    let mut result = HashMap::new();
-    get_userdata_file(steam_home, "config/shortcuts.vdf")?
-        .into_iter()
-        .for_each(|userdata_dir| match std::fs::read(&userdata_dir.path) {
-            Ok(contents) => {
-                let shortcuts = parse_names_from_bin_vdf(&contents, &POSSIBLE_KEYS, app_ids);
-                result.extend(shortcuts);
+    Shortcut {
+        app_id: shortcut
+            .get("appid")
+            .and_then(Value::as_u64)
+            .map(AppId::new)
+            .unwrap_or_else(|| legacy_app_id(&exe, &name)),
+        name,
+        exe,
+        start_dir: string("StartDir"),
+        icon: string("icon"),
+        launch_options: string("LaunchOptions"),
+        is_hidden: number("IsHidden") != 0,
+        allow_overlay: number("AllowOverlay") != 0,
+        open_vr: number("OpenVR") != 0,
+        last_play_time: number("LastPlayTime") as u32,
+        tags: shortcut
+            .get_object("tags")
+            .map(|tags| tags.iter().filter_map(|(_, t)| t.as_str()))
+            .into_iter()
+            .flatten()
+            .map(|t| t.to_string())
+            .collect(),
+    }
+}
+
+/// ```text
+/// "shortcuts"
+/// {
+///     "0" { "appid" [i32] "AppName" "..." "Exe" "..." ... "tags" { "0" "..." } }
+///     "1" { ... }
+/// }
+/// ```
+fn parse_shortcuts_vdf(contents: &[u8]) -> Result<Vec<Shortcut>> {
+    let root = bin_vdf::parse(contents)?;
+    let shortcuts = root
+        .get_object("shortcuts")
+        .ok_or("Missing 'shortcuts' section")?
+        .iter()
+        .filter_map(|(_, shortcut)| shortcut.as_object())
+        .map(parse_shortcut)
+        .collect();
+
+    Ok(shortcuts)
+}
+
+fn read_shortcuts(
+    steam_home: &Path,
+    on_error: impl Fn(&Path, Box<dyn std::error::Error>) -> Result<()>,
+) -> Result<BTreeMap<SteamId64, Vec<Shortcut>>> {
+    let mut result = BTreeMap::new();
+    for userdata_dir in get_userdata_file(steam_home, "config/shortcuts.vdf")? {
+        match std::fs::read(&userdata_dir.path)
+            .map_err(|e| e.into())
+            .and_then(|contents| parse_shortcuts_vdf(&contents))
+        {
+            Ok(shortcuts) => {
+                result.insert(userdata_dir.user_id.into(), shortcuts);
             }
-            Err(e) => error!(
-                "Failed to parse names from '{}': {}",
-                userdata_dir.path.display(),
-                e
-            ),
-        });
+            Err(e) => on_error(&userdata_dir.path, e)?,
+        }
+    }
 
     Ok(result)
 }
+
+/// Shortcuts from every user's shortcuts.vdf
+pub fn parse_shortcuts(steam_home: &Path) -> Result<BTreeMap<SteamId64, Vec<Shortcut>>> {
+    read_shortcuts(steam_home, |path, e| {
+        error!("Failed to parse shortcuts from '{}': {}", path.display(), e);
+        Ok(())
+    })
+}
+
+/// Like [`parse_shortcuts`], but errors if any user's shortcuts.vdf can't be parsed
+pub fn try_parse_shortcuts(steam_home: &Path) -> Result<BTreeMap<SteamId64, Vec<Shortcut>>> {
+    read_shortcuts(steam_home, |path, e| {
+        Err(format!("Failed to parse shortcuts from '{}': {}", path.display(), e).into())
+    })
+}
+
+/// Shortcuts from all users with a matching app id
+pub fn parse_names(steam_home: &Path, app_ids: &[&AppId]) -> Result<HashMap<AppId, Shortcut>> {
+    let result = parse_shortcuts(steam_home)?
+        .into_values()
+        .flatten()
+        .filter(|shortcut| app_ids.contains(&&shortcut.app_id))
+        .map(|shortcut| (shortcut.app_id, shortcut))
+        .collect();
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(value_type: u8, key: &str, value: &[u8]) -> Vec<u8> {
+        [&[value_type], key.as_bytes(), b"\0", value].concat()
+    }
+
+    #[test]
+    fn parses_shortcut_fields() {
+        let contents = [
+            entry(0x00, "shortcuts", &[]),
+            entry(0x00, "0", &[]),
+            entry(0x02, "appid", &2583605614u32.to_le_bytes()),
+            entry(0x01, "AppName", b"The Name1\0"),
+            entry(0x01, "Exe", b"\"/usr/bin/game\"\0"),
+            entry(0x01, "StartDir", b"\"/usr/bin/\"\0"),
+            entry(0x01, "icon", b"\0"),
+            entry(0x01, "LaunchOptions", b"-fullscreen\0"),
+            entry(0x02, "IsHidden", &1u32.to_le_bytes()),
+            entry(0x02, "AllowOverlay", &1u32.to_le_bytes()),
+            entry(0x02, "OpenVR", &0u32.to_le_bytes()),
+            entry(0x02, "LastPlayTime", &1637000000u32.to_le_bytes()),
+            entry(0x00, "tags", &[]),
+            entry(0x01, "0", b"favorite\0"),
+            vec![0x08, 0x08, 0x08, 0x08],
+        ]
+        .concat();
+
+        let shortcuts = parse_shortcuts_vdf(&contents).unwrap();
+
+        assert_eq!(
+            shortcuts,
+            vec![Shortcut {
+                app_id: AppId::new(2583605614),
+                name: "The Name1".to_string(),
+                exe: "\"/usr/bin/game\"".to_string(),
+                start_dir: "\"/usr/bin/\"".to_string(),
+                icon: "".to_string(),
+                launch_options: "-fullscreen".to_string(),
+                is_hidden: true,
+                allow_overlay: true,
+                open_vr: false,
+                last_play_time: 1637000000,
+                tags: vec!["favorite".to_string()],
+            }]
+        );
+    }
+
+    #[test]
+    fn name_key_is_case_insensitive() {
+        let contents = [
+            entry(0x00, "shortcuts", &[]),
+            entry(0x00, "0", &[]),
+            entry(0x02, "appid", &1u32.to_le_bytes()),
+            entry(0x01, "appname", b"The Name1\0"),
+            vec![0x08],
+            entry(0x00, "1", &[]),
+            entry(0x02, "appid", &2u32.to_le_bytes()),
+            entry(0x01, "AppName", b"The Name2\0"),
+            vec![0x08, 0x08, 0x08],
+        ]
+        .concat();
+
+        let shortcuts = parse_shortcuts_vdf(&contents).unwrap();
+
+        assert_eq!(shortcuts[0].name, "The Name1");
+        assert_eq!(shortcuts[1].name, "The Name2");
+    }
+
+    #[test]
+    fn derives_app_id_for_legacy_shortcuts() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        assert_eq!(
+            legacy_app_id("exe", "name"),
+            AppId::new((crc32(b"exename") | 0x80000000) as u64)
+        );
+    }
+}
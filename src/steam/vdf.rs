@@ -0,0 +1,430 @@
+//! KeyValues (VDF) tree and text format parsing. See [`super::bin_vdf`] for the binary format.
+//!
+//! ```vdf
+//! // comment
+//! "key"   "value"
+//! "section"
+//! {
+//!     unquoted_key    "value with \"escapes\""
+//!     "nested" { "key" "value" } [$LINUX]
+//! }
+//! ```
+use super::Result;
+use std::iter::Peekable;
+use std::ops::Range;
+use std::str::CharIndices;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Int32(i32),
+    Float(f32),
+    UInt64(u64),
+    Int64(i64),
+    Object(Object),
+}
+
+impl Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Numeric value of integers or numeric strings. Int32 values are reinterpreted as
+    /// unsigned since that's how Steam stores ids.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::String(s) => s.parse().ok(),
+            Value::Int32(i) => Some(*i as u32 as u64),
+            Value::UInt64(i) => Some(*i),
+            Value::Int64(i) => u64::try_from(*i).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&Object> {
+        match self {
+            Value::Object(o) => Some(o),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub key: String,
+    pub value: Value,
+    /// Location in the text it was parsed from. `None` for binary VDF.
+    pub span: Option<Span>,
+}
+
+/// Byte ranges of an entry's tokens, including quotes and braces
+#[derive(Clone, Debug, PartialEq)]
+pub struct Span {
+    pub key: Range<usize>,
+    pub value: Range<usize>,
+}
+
+/// An ordered list of key value pairs. Keys are compared case insensitively and may be duplicated.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Object(Vec<Entry>);
+
+impl From<Vec<Entry>> for Object {
+    fn from(entries: Vec<Entry>) -> Self {
+        Self(entries)
+    }
+}
+
+impl Object {
+    pub fn entries(&self) -> &[Entry] {
+        &self.0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.0.iter().map(|e| (e.key.as_str(), &e.value))
+    }
+
+    /// First value with a matching key
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.0
+            .iter()
+            .find(|e| e.key.eq_ignore_ascii_case(key))
+            .map(|e| &e.value)
+    }
+
+    pub fn get_object(&self, key: &str) -> Option<&Object> {
+        self.get(key).and_then(Value::as_object)
+    }
+
+    /// Follows each key of `path` through nested objects
+    pub fn get_path(&self, path: &[&str]) -> Option<&Value> {
+        let (last, parents) = path.split_last()?;
+        parents
+            .iter()
+            .try_fold(self, |obj, key| obj.get_object(key))
+            .and_then(|obj| obj.get(last))
+    }
+
+    /// Depth-first search for the first object with a matching key, at any depth
+    pub fn find_object(&self, key: &str) -> Option<&Object> {
+        self.0.iter().find_map(|e| match &e.value {
+            Value::Object(o) if e.key.eq_ignore_ascii_case(key) => Some(o),
+            Value::Object(o) => o.find_object(key),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    String(String),
+    OpenBrace,
+    CloseBrace,
+    Conditional,
+}
+
+struct Tokenizer<'a> {
+    input: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            chars: input.char_indices().peekable(),
+        }
+    }
+
+    /// Byte offset of the next character
+    fn position(&mut self) -> usize {
+        self.chars.peek().map_or(self.input.len(), |&(i, _)| i)
+    }
+
+    fn line_of(&self, pos: usize) -> usize {
+        self.input[..pos].matches('\n').count() + 1
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        while let Some(&(i, c)) = self.chars.peek() {
+            if c.is_whitespace() || c == '\u{feff}' {
+                self.chars.next();
+            } else if self.input[i..].starts_with("//") {
+                while self.chars.next_if(|&(_, c)| c != '\n').is_some() {}
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn quoted(&mut self, start: usize) -> Result<String> {
+        let mut s = String::new();
+        loop {
+            match self.chars.next() {
+                Some((_, '"')) => return Ok(s),
+                Some((_, '\\')) => match self.chars.next() {
+                    Some((_, 'n')) => s.push('\n'),
+                    Some((_, 't')) => s.push('\t'),
+                    Some((_, '\\')) => s.push('\\'),
+                    Some((_, '"')) => s.push('"'),
+                    Some((_, c)) => {
+                        s.push('\\');
+                        s.push(c);
+                    }
+                    None => break,
+                },
+                Some((_, c)) => s.push(c),
+                None => break,
+            }
+        }
+
+        Err(format!(
+            "Unterminated string starting on line {}",
+            self.line_of(start)
+        )
+        .into())
+    }
+
+    fn unquoted(&mut self, start: usize) -> String {
+        let mut end = start;
+        while let Some((i, c)) = self
+            .chars
+            .next_if(|&(_, c)| !c.is_whitespace() && !matches!(c, '"' | '{' | '}'))
+        {
+            end = i + c.len_utf8();
+        }
+
+        self.input[start..end].to_string()
+    }
+
+    fn next_token(&mut self) -> Result<Option<(usize, Token)>> {
+        self.skip_whitespace_and_comments();
+        let Some(&(start, c)) = self.chars.peek() else {
+            return Ok(None);
+        };
+
+        let token = match c {
+            '{' => {
+                self.chars.next();
+                Token::OpenBrace
+            }
+            '}' => {
+                self.chars.next();
+                Token::CloseBrace
+            }
+            '"' => {
+                self.chars.next();
+                Token::String(self.quoted(start)?)
+            }
+            '[' => {
+                while self.chars.next_if(|&(_, c)| c != ']').is_some() {}
+                self.chars.next();
+                Token::Conditional
+            }
+            _ => Token::String(self.unquoted(start)),
+        };
+
+        Ok(Some((start, token)))
+    }
+}
+
+struct Parser<'a> {
+    tokens: Tokenizer<'a>,
+    peeked: Option<(usize, Token)>,
+}
+
+impl<'a> Parser<'a> {
+    fn next(&mut self) -> Result<Option<(usize, Token)>> {
+        match self.peeked.take() {
+            Some(t) => Ok(Some(t)),
+            None => self.tokens.next_token(),
+        }
+    }
+
+    fn skip_conditional(&mut self) -> Result<()> {
+        match self.next()? {
+            Some((_, Token::Conditional)) | None => {}
+            other => self.peeked = other,
+        }
+        Ok(())
+    }
+
+    fn object(&mut self, nested: bool) -> Result<Object> {
+        let mut entries = Vec::new();
+        loop {
+            let (key, key_start) = match self.next()? {
+                Some((start, Token::String(key))) => (key, start),
+                Some((_, Token::CloseBrace)) if nested => return Ok(Object(entries)),
+                None if !nested => return Ok(Object(entries)),
+                None => return Err("Unexpected end of file, expected '}'".into()),
+                Some((pos, t)) => {
+                    return Err(format!(
+                        "Unexpected {:?} on line {}, expected a key",
+                        t,
+                        self.tokens.line_of(pos)
+                    )
+                    .into())
+                }
+            };
+            let key_end = self.tokens.position();
+
+            let (value, value_start) = match self.next()? {
+                Some((start, Token::String(value))) => (Value::String(value), start),
+                Some((start, Token::OpenBrace)) => (Value::Object(self.object(true)?), start),
+                Some((pos, t)) => {
+                    return Err(format!(
+                        "Unexpected {:?} on line {}, expected a value for '{}'",
+                        t,
+                        self.tokens.line_of(pos),
+                        key
+                    )
+                    .into())
+                }
+                None => {
+                    return Err(
+                        format!("Unexpected end of file, expected a value for '{}'", key).into(),
+                    )
+                }
+            };
+            let span = Span {
+                key: key_start..key_end,
+                value: value_start..self.tokens.position(),
+            };
+            self.skip_conditional()?;
+
+            entries.push(Entry {
+                key,
+                value,
+                span: Some(span),
+            });
+        }
+    }
+}
+
+/// Parses the contents of a text VDF file into its root object
+pub fn parse(input: &str) -> Result<Object> {
+    let mut parser = Parser {
+        tokens: Tokenizer::new(input),
+        peeked: None,
+    };
+
+    parser.object(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nested_sections() {
+        let root = parse(
+            r#"
+            "Root"
+            {
+                "Section"
+                {
+                    "key"   "value"
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            root.get_path(&["root", "section", "KEY"]),
+            Some(&Value::String("value".to_string()))
+        );
+    }
+
+    #[test]
+    fn parses_inline_braces_and_unquoted_tokens() {
+        let root = parse(r#"Section { key value "other" { "a" "1" } }"#).unwrap();
+        let section = root.get_object("Section").unwrap();
+
+        assert_eq!(section.get("key").and_then(Value::as_str), Some("value"));
+        assert_eq!(
+            section.get_path(&["other", "a"]).and_then(Value::as_str),
+            Some("1")
+        );
+    }
+
+    #[test]
+    fn ignores_comments_and_conditionals() {
+        let root = parse(
+            r#"
+            // leading comment
+            "Section" // trailing comment
+            {
+                "key"   "value" [$WIN32]
+                "url"   "http://example.com"
+            }"#,
+        )
+        .unwrap();
+        let section = root.get_object("Section").unwrap();
+
+        assert_eq!(section.get("key").and_then(Value::as_str), Some("value"));
+        assert_eq!(
+            section.get("url").and_then(Value::as_str),
+            Some("http://example.com")
+        );
+    }
+
+    #[test]
+    fn unescapes_quoted_strings() {
+        let root = parse(r#""key" "-a \"b c\" \\d\te""#).unwrap();
+
+        assert_eq!(
+            root.get("key").and_then(Value::as_str),
+            Some("-a \"b c\" \\d\te")
+        );
+    }
+
+    #[test]
+    fn keeps_unknown_escapes() {
+        let root = parse(r#""key" "C:\Games""#).unwrap();
+
+        assert_eq!(root.get("key").and_then(Value::as_str), Some("C:\\Games"));
+    }
+
+    #[test]
+    fn find_object_searches_nested_sections() {
+        let root = parse(r#""a" { "b" { "c" { "key" "value" } } }"#).unwrap();
+
+        assert_eq!(
+            root.find_object("C")
+                .and_then(|c| c.get("key").and_then(Value::as_str)),
+            Some("value")
+        );
+    }
+
+    #[test]
+    fn spans_cover_tokens() {
+        let input = r#""Section" { "key" "value" }"#;
+        let root = parse(input).unwrap();
+        let section = &root.entries()[0];
+        let key = &section.value.as_object().unwrap().entries()[0];
+
+        let span = section.span.as_ref().unwrap();
+        assert_eq!(&input[span.key.clone()], r#""Section""#);
+        assert_eq!(&input[span.value.clone()], r#"{ "key" "value" }"#);
+        let span = key.span.as_ref().unwrap();
+        assert_eq!(&input[span.key.clone()], r#""key""#);
+        assert_eq!(&input[span.value.clone()], r#""value""#);
+    }
+
+    #[test]
+    fn errors_on_unclosed_section() {
+        assert!(parse(r#""Section" { "key" "value""#).is_err());
+    }
+
+    #[test]
+    fn errors_on_unterminated_string() {
+        assert!(parse(r#""Section" { "key" "value }"#).is_err());
+    }
+
+    #[test]
+    fn errors_on_missing_value() {
+        assert!(parse(r#""Section" { "key" }"#).is_err());
+    }
+}
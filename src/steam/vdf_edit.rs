@@ -0,0 +1,274 @@
+//! In place editing of text VDF. Everything outside of the edited entries is kept
+//! byte-for-byte, including comments, whitespace and key order.
+use super::vdf::{self, Object, Value};
+use super::Result;
+
+/// Quotes a string, escaping characters the same way Steam does
+fn quote(s: &str) -> String {
+    let escaped = s
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+
+    format!("\"{}\"", escaped)
+}
+
+/// One entry per line, in the layout Steam writes
+/// ```vdf
+/// [indent]"key"
+/// [indent]{
+/// [indent]    "key"       "value"
+/// [indent]}
+/// ```
+fn format_entries(path: &[&str], value: &str, indent: &str) -> String {
+    match path {
+        [key] => format!("{}{}\t\t{}\n", indent, quote(key), quote(value)),
+        [key, rest @ ..] => format!(
+            "{indent}{}\n{indent}{{\n{}{indent}}}\n",
+            quote(key),
+            format_entries(rest, value, &format!("{}\t", indent)),
+            indent = indent
+        ),
+        [] => String::new(),
+    }
+}
+
+fn format_inline(path: &[&str], value: &str) -> String {
+    match path {
+        [key] => format!("{} {}", quote(key), quote(value)),
+        [key, rest @ ..] => format!("{} {{ {} }}", quote(key), format_inline(rest, value)),
+        [] => String::new(),
+    }
+}
+
+fn line_start(text: &str, pos: usize) -> usize {
+    text[..pos].rfind('\n').map_or(0, |i| i + 1)
+}
+
+/// A text VDF file that can be edited without reformatting it
+pub struct Document {
+    text: String,
+}
+
+impl Document {
+    pub fn parse(text: String) -> Result<Self> {
+        vdf::parse(&text)?;
+        Ok(Self { text })
+    }
+
+    pub fn root(&self) -> Result<Object> {
+        vdf::parse(&self.text)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn into_string(self) -> String {
+        self.text
+    }
+
+    /// Sets the string value at `path`, creating any missing sections. Keys are matched
+    /// case insensitively and the first match is used.
+    pub fn set(&mut self, path: &[&str], value: &str) -> Result<()> {
+        let root = self.root()?;
+        let mut object = &root;
+        // Offset of the current object's closing brace. `None` for the root.
+        let mut close_brace = None;
+
+        for (i, key) in path.iter().enumerate() {
+            let is_last = i == path.len() - 1;
+            let entry = object
+                .entries()
+                .iter()
+                .find(|e| e.key.eq_ignore_ascii_case(key));
+            let Some(entry) = entry else {
+                self.insert(close_brace, &path[i..], value);
+                return Ok(());
+            };
+            let span = entry.span.as_ref().ok_or("Entry has no location")?;
+
+            match (&entry.value, is_last) {
+                (Value::String(_), true) => {
+                    self.text.replace_range(span.value.clone(), &quote(value));
+                    return Ok(());
+                }
+                (Value::Object(o), false) => {
+                    object = o;
+                    close_brace = Some(span.value.end - 1);
+                }
+                (_, true) => return Err(format!("'{}' is a section, not a value", key).into()),
+                (_, false) => return Err(format!("'{}' is a value, not a section", key).into()),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn insert(&mut self, close_brace: Option<usize>, path: &[&str], value: &str) {
+        match close_brace {
+            Some(pos) => {
+                let start = line_start(&self.text, pos);
+                let before_brace = &self.text[start..pos];
+                if before_brace.trim().is_empty() {
+                    let indent = format!("{}\t", before_brace);
+                    let entries = format_entries(path, value, &indent);
+                    self.text.insert_str(start, &entries);
+                } else {
+                    let entries = format!("{} ", format_inline(path, value));
+                    self.text.insert_str(pos, &entries);
+                }
+            }
+            None => {
+                if !self.text.is_empty() && !self.text.ends_with('\n') {
+                    self.text.push('\n');
+                }
+                self.text.push_str(&format_entries(path, value, ""));
+            }
+        }
+    }
+
+    /// Removes the entry at `path`, along with its line if nothing else is on it.
+    /// Returns false if there's no such entry.
+    pub fn remove(&mut self, path: &[&str]) -> Result<bool> {
+        let root = self.root()?;
+        let Some((last, parents)) = path.split_last() else {
+            return Ok(false);
+        };
+        let parent = match parents {
+            [] => Some(&root),
+            _ => root.get_path(parents).and_then(Value::as_object),
+        };
+        let span = parent
+            .and_then(|p| {
+                p.entries()
+                    .iter()
+                    .find(|e| e.key.eq_ignore_ascii_case(last))
+            })
+            .and_then(|e| e.span.clone());
+        let Some(span) = span else {
+            return Ok(false);
+        };
+
+        let (mut start, mut end) = (span.key.start, span.value.end);
+        let line_end = self.text[end..].find('\n').map(|i| end + i + 1);
+        let own_line = self.text[line_start(&self.text, start)..start]
+            .trim()
+            .is_empty()
+            && self.text[end..line_end.unwrap_or(self.text.len())]
+                .trim()
+                .is_empty();
+        if own_line {
+            start = line_start(&self.text, start);
+            end = line_end.unwrap_or(self.text.len());
+        }
+        self.text.replace_range(start..end, "");
+
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str =
+        "\"Root\"\n{\n\t// comment\n\t\"Section\"\n\t{\n\t\t\"key\"\t\t\"value\"\n\t}\n}\n";
+
+    fn edit(text: &str, f: impl FnOnce(&mut Document)) -> String {
+        let mut document = Document::parse(text.to_string()).unwrap();
+        f(&mut document);
+        document.into_string()
+    }
+
+    #[test]
+    fn replaces_existing_value() {
+        let result = edit(CONFIG, |d| {
+            d.set(&["root", "section", "KEY"], "new").unwrap()
+        });
+
+        assert_eq!(result, CONFIG.replace("\"value\"", "\"new\""));
+    }
+
+    #[test]
+    fn escapes_values() {
+        let result = edit(CONFIG, |d| {
+            d.set(&["Root", "Section", "key"], "-a \"b\" C:\\c")
+                .unwrap()
+        });
+
+        assert!(result.contains(r#""key"		"-a \"b\" C:\\c""#));
+        let root = vdf::parse(&result).unwrap();
+        assert_eq!(
+            root.get_path(&["Root", "Section", "key"])
+                .and_then(Value::as_str),
+            Some("-a \"b\" C:\\c")
+        );
+    }
+
+    #[test]
+    fn inserts_missing_sections_with_indentation() {
+        let result = edit(CONFIG, |d| {
+            d.set(&["Root", "Section", "new", "a"], "1").unwrap()
+        });
+
+        assert_eq!(
+            result,
+            "\"Root\"\n{\n\t// comment\n\t\"Section\"\n\t{\n\t\t\"key\"\t\t\"value\"\n\t\t\"new\"\n\t\t{\n\t\t\t\"a\"\t\t\"1\"\n\t\t}\n\t}\n}\n"
+        );
+    }
+
+    #[test]
+    fn inserts_into_inline_sections() {
+        let result = edit(r#""Root" { "a" "1" }"#, |d| {
+            d.set(&["Root", "b"], "2").unwrap()
+        });
+
+        assert_eq!(result, r#""Root" { "a" "1" "b" "2" }"#);
+    }
+
+    #[test]
+    fn inserts_into_root() {
+        let result = edit(CONFIG, |d| d.set(&["Other", "a"], "1").unwrap());
+
+        assert_eq!(
+            result,
+            format!("{}\"Other\"\n{{\n\t\"a\"\t\t\"1\"\n}}\n", CONFIG)
+        );
+    }
+
+    #[test]
+    fn errors_when_setting_a_section() {
+        let mut document = Document::parse(CONFIG.to_string()).unwrap();
+
+        assert!(document.set(&["Root", "Section"], "1").is_err());
+        assert!(document.set(&["Root", "Section", "key", "a"], "1").is_err());
+        assert_eq!(document.as_str(), CONFIG);
+    }
+
+    #[test]
+    fn removes_entry_and_its_line() {
+        let result = edit(CONFIG, |d| assert!(d.remove(&["Root", "Section"]).unwrap()));
+
+        assert_eq!(result, "\"Root\"\n{\n\t// comment\n}\n");
+    }
+
+    #[test]
+    fn removes_inline_entry() {
+        let result = edit(r#""Root" { "a" "1" "b" "2" }"#, |d| {
+            assert!(d.remove(&["Root", "a"]).unwrap())
+        });
+
+        assert_eq!(result, r#""Root" {  "b" "2" }"#);
+    }
+
+    #[test]
+    fn remove_returns_false_if_missing() {
+        let result = edit(CONFIG, |d| {
+            assert!(!d.remove(&["Root", "missing"]).unwrap())
+        });
+
+        assert_eq!(result, CONFIG);
+    }
+}